@@ -8,6 +8,7 @@ use itertools::Itertools;
 use std::fs::read_to_string;
 use stoichkit::ext::parse_chemdraw_reaction;
 use stoichkit::model::{Compound, Substance, YieldReaction};
+use stoichkit::sigfig::format_sig_figs;
 use stoichkit::solve::balance;
 
 #[derive(Clap)]
@@ -23,6 +24,8 @@ struct ReactionList {
         about = "Fully balanced chemical reaction list: [<coeff>*]<formula> <grams>, coeff defaults to 1"
     )]
     substances: Vec<String>,
+    #[clap(long, about = "Round the yield to this many significant figures")]
+    sig_figs: Option<u32>,
 }
 
 #[derive(Clap)]
@@ -47,20 +50,26 @@ impl Unbal {
                     .substances
                     .iter()
                     .take_while(|a| a.as_str() != "=")
-                    .map(|f| Compound::from_formula(f.as_str()))
+                    .map(|f| {
+                        Compound::from_formula(f.as_str())
+                            .map_err(|e| e.to_string())
+                    })
                     .collect();
                 let rx_len = reagent_input.clone()?.len() + 1;
                 let product_input: Result<Vec<Compound>, _> = self
                     .substances
                     .iter()
                     .dropping(rx_len)
-                    .map(|f| Compound::from_formula(f.as_str()))
+                    .map(|f| {
+                        Compound::from_formula(f.as_str())
+                            .map_err(|e| e.to_string())
+                    })
                     .collect();
                 match (
                     reagent_input.clone()?.len(),
                     product_input.clone()?.len(),
                 ) {
-                    (x, y) if x >= 1 as usize && y >= 1 as usize => Ok(()),
+                    (x, y) if x >= 1 && y >= 1 => Ok(()),
                     _ => Err("Must provide at least 1 reactant and 1 product"),
                 }?;
                 (reagent_input?, product_input?)
@@ -68,7 +77,8 @@ impl Unbal {
             Some(f) => {
                 let s = read_to_string(f)
                     .map_err(|_e| format!("Could not read file {:?}", f))?;
-                let result = parse_chemdraw_reaction(s.as_str())?;
+                let result = parse_chemdraw_reaction(s.as_str())
+                    .map_err(|e| e.to_string())?;
                 info!(
                     "Parsed reaction {:?} = {:?}",
                     result.reactants, result.products
@@ -76,7 +86,8 @@ impl Unbal {
                 (result.reactants, result.products)
             }
         };
-        let balanced = balance(reagents, products)?;
+        let balanced =
+            balance(reagents, products).map_err(|e| e.to_string())?;
         Ok(balanced.display_string())
     }
 }
@@ -104,7 +115,7 @@ impl ReactionList {
                             pair[0]
                         )
                     })?,
-                    stoich.iter().cloned().last().unwrap(),
+                    stoich.last().cloned().unwrap(),
                 ),
                 _ => {
                     return Err(format!(
@@ -116,20 +127,24 @@ impl ReactionList {
             };
             let substance = Substance::from_formula(
                 formula,
-                pair[1].clone().parse::<f32>().map_err(|_| {
+                pair[1].clone().parse::<f64>().map_err(|_| {
                     format!(
                         "Invalid mass {} for substance {}",
                         pair[1], pair[0]
                     )
                 })?,
                 coeff,
-            )?;
+            )
+            .map_err(|e| e.to_string())?;
             substances.push(substance);
         }
         let (product, reagents) = substances
             .split_last()
             .ok_or_else(|| "Invalid substance list!".to_string())?;
-        Ok(YieldReaction::new(reagents.to_vec(), product.to_owned()))
+        Ok(YieldReaction::new(
+            reagents.to_vec(),
+            vec![product.to_owned()],
+        ))
     }
 }
 
@@ -137,8 +152,17 @@ fn main() {
     env_logger::Builder::from_env("STOICHKIT_LOG").init();
     let opts: Cli = Cli::parse();
     match opts.command {
-        Subcommand::Yield(r) => match r.reaction().map(|r| r.percent_yield()) {
-            Ok(yld) => println!("Yield: {:?}", yld),
+        Subcommand::Yield(r) => match r
+            .reaction()
+            .and_then(|r| r.percent_yield().map_err(|e| e.to_string()))
+        {
+            Ok(yld) => match r.sig_figs {
+                Some(figures) => match format_sig_figs(yld, figures) {
+                    Ok(yld) => println!("Yield: {}", yld),
+                    Err(e) => println!("ERROR: {:?}", e.to_string()),
+                },
+                None => println!("Yield: {:?}", yld),
+            },
             Err(msg) => println!("ERROR: {:?}", msg),
         },
         Subcommand::Balance(u) => {
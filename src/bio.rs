@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use crate::error::StoichError;
+use crate::model::{Compound, Element};
+use crate::molecule::formula_string;
+use crate::parse::{get_element, parse_formula};
+
+// One-letter code, three-letter code and the free amino acid's formula.
+const AMINO_ACIDS: [(char, &str, &str); 20] = [
+    ('A', "Ala", "C3H7NO2"),
+    ('R', "Arg", "C6H14N4O2"),
+    ('N', "Asn", "C4H8N2O3"),
+    ('D', "Asp", "C4H7NO4"),
+    ('C', "Cys", "C3H7NO2S"),
+    ('E', "Glu", "C5H9NO4"),
+    ('Q', "Gln", "C5H10N2O3"),
+    ('G', "Gly", "C2H5NO2"),
+    ('H', "His", "C6H9N3O2"),
+    ('I', "Ile", "C6H13NO2"),
+    ('L', "Leu", "C6H13NO2"),
+    ('K', "Lys", "C6H14N2O2"),
+    ('M', "Met", "C5H11NO2S"),
+    ('F', "Phe", "C9H11NO2"),
+    ('P', "Pro", "C5H9NO2"),
+    ('S', "Ser", "C3H7NO3"),
+    ('T', "Thr", "C4H9NO3"),
+    ('W', "Trp", "C11H12N2O2"),
+    ('Y', "Tyr", "C9H11NO3"),
+    ('V', "Val", "C5H11NO2"),
+];
+
+// Formulas of the residues in `sequence`, written with one-letter codes
+// (`GAS`) or three-letter codes (`GlyAlaSer` or `Gly-Ala-Ser`). Without a
+// hyphen or a lowercase letter the sequence is one-letter code.
+fn residues(sequence: &str) -> Result<Vec<&'static str>, StoichError> {
+    let codes: Vec<char> = sequence
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if codes.is_empty() {
+        return Err(StoichError::InvalidInput(
+            "Peptide sequence is empty".to_string(),
+        ));
+    }
+    let three_letter = sequence.contains('-')
+        || sequence.chars().any(|c| c.is_ascii_lowercase());
+    if !three_letter {
+        return codes
+            .iter()
+            .map(|code| {
+                AMINO_ACIDS
+                    .iter()
+                    .find(|(one, _, _)| one == code)
+                    .map(|(_, _, formula)| *formula)
+                    .ok_or_else(|| {
+                        StoichError::InvalidInput(format!(
+                            "Unknown amino acid {:?}",
+                            code
+                        ))
+                    })
+            })
+            .collect();
+    }
+    if !codes.len().is_multiple_of(3) {
+        return Err(StoichError::InvalidInput(format!(
+            "Sequence {:?} is not made of three-letter codes",
+            sequence
+        )));
+    }
+    codes
+        .chunks(3)
+        .map(|chunk| {
+            let code: String = chunk.iter().collect();
+            AMINO_ACIDS
+                .iter()
+                .find(|(_, three, _)| three.eq_ignore_ascii_case(&code))
+                .map(|(_, _, formula)| *formula)
+                .ok_or_else(|| {
+                    StoichError::InvalidInput(format!(
+                        "Unknown amino acid {:?}",
+                        code
+                    ))
+                })
+        })
+        .collect()
+}
+
+/// The linear peptide with the given sequence: its amino acids joined by
+/// peptide bonds, each of which gives off one water. Three-letter codes
+/// need a hyphen or lowercase letters: `GLYALA` is read as the one-letter
+/// codes G, L, Y, A, L, A.
+pub fn peptide(sequence: &str) -> Result<Compound, StoichError> {
+    let residues = residues(sequence)?;
+    let mut atoms: BTreeMap<Element, u32> = BTreeMap::new();
+    for formula in &residues {
+        for (element, count) in parse_formula(formula)? {
+            *atoms.entry(element).or_insert(0) += count;
+        }
+    }
+    let bonds = residues.len() as u32 - 1;
+    for (symbol, count) in [("H", 2 * bonds), ("O", bonds)] {
+        *atoms.get_mut(&get_element(symbol)?).unwrap() -= count;
+    }
+    Compound::from_formula(&formula_string(&atoms))
+}
+
+/// Molecular weight in g/mol of the peptide with the given sequence.
+pub fn peptide_molecular_weight(sequence: &str) -> Result<f64, StoichError> {
+    peptide(sequence)?.molecular_weight()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bio::{peptide, peptide_molecular_weight};
+
+    #[test]
+    fn dipeptide() {
+        assert_eq!(peptide("GG").unwrap().formula, "C4H8N2O3");
+        let weight = peptide_molecular_weight("Gly-Gly").unwrap();
+        assert!((weight - 132.12).abs() < 0.01, "{}", weight);
+        assert_eq!(peptide("A").unwrap().formula, "C3H7NO2");
+    }
+
+    #[test]
+    fn leu_enkephalin() {
+        let one_letter = peptide_molecular_weight("YGGFL").unwrap();
+        let three_letter = peptide_molecular_weight("TyrGlyGlyPheLeu").unwrap();
+        assert_eq!(one_letter, three_letter);
+        assert!((one_letter - 555.63).abs() < 0.01, "{}", one_letter);
+        assert_eq!(peptide("YGGFL").unwrap().formula, "C28H37N5O7");
+    }
+
+    #[test]
+    fn capitals_are_one_letter_code() {
+        // Gly-Leu-Tyr-Ala-Leu-Ala, not Gly-Ala.
+        assert_eq!(peptide("GLYALA").unwrap().formula, "C29H46N6O8");
+        assert_eq!(peptide("GlyAla").unwrap().formula, "C5H10N2O3");
+        assert_eq!(peptide("GLY-ALA").unwrap().formula, "C5H10N2O3");
+    }
+
+    #[test]
+    fn invalid_sequences() {
+        assert!(peptide("").is_err());
+        assert!(peptide("GXG").is_err());
+        assert!(peptide("Gly-Al").is_err());
+        assert!(peptide("Gly-Xyz").is_err());
+    }
+}
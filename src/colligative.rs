@@ -0,0 +1,87 @@
+use crate::error::StoichError;
+use crate::model::Substance;
+
+/// Ebullioscopic constant of water in K·kg/mol.
+pub const WATER_KB: f64 = 0.512;
+/// Cryoscopic constant of water in K·kg/mol.
+pub const WATER_KF: f64 = 1.86;
+
+fn check_solute(molality: f64, i: f64) -> Result<(), StoichError> {
+    if molality.is_nan() || molality < 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Molality must not be negative, got {}",
+            molality
+        )));
+    }
+    if i.is_nan() || i <= 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Van 't Hoff factor must be positive, got {}",
+            i
+        )));
+    }
+    Ok(())
+}
+
+/// Moles of `solute` per kilogram when dissolved in `solvent_mass_g` grams
+/// of solvent.
+pub fn molality(
+    solute: &Substance,
+    solvent_mass_g: f64,
+) -> Result<f64, StoichError> {
+    if solvent_mass_g.is_nan() || solvent_mass_g <= 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Solvent mass must be positive, got {} g",
+            solvent_mass_g
+        )));
+    }
+    Ok(solute.moles() / (solvent_mass_g / 1000.0))
+}
+
+/// Rise in boiling point in K, `i * kb * molality`, where `i` is the van 't
+/// Hoff factor.
+pub fn bp_elevation(
+    kb: f64,
+    molality: f64,
+    i: f64,
+) -> Result<f64, StoichError> {
+    check_solute(molality, i)?;
+    Ok(i * kb * molality)
+}
+
+/// Drop in freezing point in K, `i * kf * molality`, given as a positive
+/// number.
+pub fn fp_depression(
+    kf: f64,
+    molality: f64,
+    i: f64,
+) -> Result<f64, StoichError> {
+    check_solute(molality, i)?;
+    Ok(i * kf * molality)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::colligative::{
+        bp_elevation, fp_depression, molality, WATER_KB, WATER_KF,
+    };
+    use crate::model::Substance;
+
+    #[test]
+    fn salt_water() {
+        let salt = Substance::from_formula("NaCl", 58.44, 1).unwrap();
+        let m = molality(&salt, 1000.0).unwrap();
+        assert!((m - 1.0).abs() < 1e-3, "{}", m);
+        let rise = bp_elevation(WATER_KB, m, 2.0).unwrap();
+        assert!((rise - 1.024).abs() < 1e-3, "{}", rise);
+        let drop = fp_depression(WATER_KF, m, 2.0).unwrap();
+        assert!((drop - 3.72).abs() < 1e-2, "{}", drop);
+    }
+
+    #[test]
+    fn invalid_solutions() {
+        let salt = Substance::from_formula("NaCl", 58.44, 1).unwrap();
+        assert!(molality(&salt, 0.0).is_err());
+        assert!(bp_elevation(WATER_KB, -1.0, 2.0).is_err());
+        assert!(fp_depression(WATER_KF, 1.0, 0.0).is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "balance")]
+use rug::Rational;
+
+/// Shorthand for results carrying a `StoichError`.
+pub type Result<T> = std::result::Result<T, StoichError>;
+
+/// Everything that can go wrong in parsing, balancing or calculating. The
+/// variants depend on features (`Underdetermined` needs `balance`), so
+/// matches outside this crate need a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StoichError {
+    /// Some elements appear on only one side of the equation. Each list is
+    /// sorted by symbol, with isotopes written like `^13C`.
+    UnbalanceableElements {
+        missing_products: Vec<String>,
+        missing_reagents: Vec<String>,
+    },
+    /// The equation has no solution with positive coefficients.
+    Unbalanceable(String),
+    /// The equation has several independent solutions, given as a basis of
+    /// coefficient vectors over reagents followed by products.
+    #[cfg(feature = "balance")]
+    Underdetermined(Vec<Vec<Rational>>),
+    /// `position` is the byte offset into the parsed input.
+    ParseError {
+        position: usize,
+        msg: String,
+    },
+    /// A coefficient or atom count that does not fit in a `u32`.
+    Overflow(String),
+    UnknownElement(String),
+    InvalidAtomicNumber(u8),
+    /// Masses, percentages or other arguments that cannot be used.
+    InvalidInput(String),
+}
+
+impl StoichError {
+    pub(crate) fn parse(position: usize, msg: String) -> Self {
+        StoichError::ParseError { position, msg }
+    }
+
+    /// The offset of a parse error within its input, if it has one.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            StoichError::ParseError { position, .. } => Some(*position),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for StoichError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoichError::UnbalanceableElements {
+                missing_products,
+                missing_reagents,
+            } => {
+                let sides = [
+                    (
+                        "Reagent elements missing from products",
+                        missing_products,
+                    ),
+                    (
+                        "Product elements missing from reagents",
+                        missing_reagents,
+                    ),
+                ];
+                let missing: Vec<String> = sides
+                    .iter()
+                    .filter(|(_, symbols)| !symbols.is_empty())
+                    .map(|(side, symbols)| {
+                        format!("{}: {}", side, symbols.join(", "))
+                    })
+                    .collect();
+                write!(f, "Equation cannot be balanced. {}", missing.join(". "))
+            }
+            StoichError::Unbalanceable(msg) => write!(f, "{}", msg),
+            #[cfg(feature = "balance")]
+            StoichError::Underdetermined(basis) => write!(
+                f,
+                "Equation has {} independent solutions: {:?}",
+                basis.len(),
+                basis
+            ),
+            StoichError::ParseError { msg, .. } => write!(f, "{}", msg),
+            StoichError::Overflow(value) => {
+                write!(f, "{} does not fit in u32", value)
+            }
+            StoichError::UnknownElement(symbol) => {
+                write!(f, "Invalid symbol {}", symbol)
+            }
+            StoichError::InvalidAtomicNumber(id) => {
+                write!(f, "Invalid atomic number {}", id)
+            }
+            StoichError::InvalidInput(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl Error for StoichError {}
@@ -1,3 +1,4 @@
+use crate::error::StoichError;
 use crate::model::Compound;
 use serde::Deserialize;
 
@@ -11,8 +12,13 @@ impl Reaction {
     pub fn reactants(&self) -> Vec<String> {
         self.steps
             .first()
-            .map(|s| s.reactants.iter().chain(s.reagents.iter()).collect())
-            .unwrap_or(Vec::new())
+            .map(|s| {
+                s.reactants
+                    .iter()
+                    .chain(s.reagents.iter())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
             .iter()
             .map(|m| m.formula())
             .collect()
@@ -40,6 +46,7 @@ struct Step {
 }
 
 #[derive(Deserialize, Debug)]
+#[allow(dead_code)]
 struct Molecule {
     #[serde(rename(deserialize = "NAME"))]
     name: String,
@@ -63,20 +70,24 @@ pub struct ParsedReaction {
 
 pub fn parse_chemdraw_reaction(
     document: &str,
-) -> Result<ParsedReaction, String> {
-    let parsed: Vec<Reaction> = serde_json::from_str(document)
-        .map_err(|e| format!("Could not parse: {:?}", e))?;
-    let rxn = parsed.first().ok_or("No reactions!".to_string())?;
+) -> Result<ParsedReaction, StoichError> {
+    let parsed: Vec<Reaction> =
+        serde_json::from_str(document).map_err(|e| {
+            StoichError::parse(0, format!("Could not parse: {:?}", e))
+        })?;
+    let rxn = parsed.first().ok_or_else(|| {
+        StoichError::InvalidInput("No reactions!".to_string())
+    })?;
     let reactants: Vec<Compound> = rxn
         .reactants()
         .iter()
         .map(|r| Compound::from_formula(r))
-        .collect::<Result<Vec<Compound>, String>>()?;
+        .collect::<Result<Vec<Compound>, StoichError>>()?;
     let products: Vec<Compound> = rxn
         .products()
         .iter()
         .map(|r| Compound::from_formula(r))
-        .collect::<Result<Vec<Compound>, String>>()?;
+        .collect::<Result<Vec<Compound>, StoichError>>()?;
     Ok(ParsedReaction {
         reactants,
         products,
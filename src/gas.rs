@@ -0,0 +1,410 @@
+use crate::error::StoichError;
+use crate::model::{BalancedReaction, Reactant, State};
+use crate::parse::parse_state;
+
+/// Liters per mole of an ideal gas at 0 °C and 1 atm.
+pub const STP_MOLAR_VOLUME: f64 = 22.414;
+
+// Gases at STP, for species written without a state symbol.
+const GASES: [&str; 22] = [
+    "H2", "He", "N2", "O2", "O3", "F2", "Ne", "Cl2", "Ar", "Kr", "Xe", "Rn",
+    "CO", "CO2", "NO", "NO2", "N2O", "SO2", "NH3", "CH4", "C2H6", "C3H8",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PressureUnit {
+    Atm,
+    KPa,
+}
+
+impl PressureUnit {
+    /// One bar in this unit.
+    pub fn bar(&self) -> f64 {
+        match self {
+            PressureUnit::Atm => 1.0 / 1.013_25,
+            PressureUnit::KPa => 100.0,
+        }
+    }
+
+    /// R in L·unit/(mol·K).
+    pub fn gas_constant(&self) -> f64 {
+        match self {
+            PressureUnit::Atm => 0.082_057,
+            PressureUnit::KPa => 8.314_462,
+        }
+    }
+}
+
+// Van der Waals constants: a in L²·bar/mol², b in L/mol.
+const VAN_DER_WAALS: [(&str, f64, f64); 15] = [
+    ("He", 0.0346, 0.0238),
+    ("Ne", 0.208, 0.01672),
+    ("Ar", 1.355, 0.03201),
+    ("Kr", 2.318, 0.03978),
+    ("Xe", 4.250, 0.05105),
+    ("H2", 0.2476, 0.02661),
+    ("N2", 1.370, 0.0387),
+    ("O2", 1.382, 0.03186),
+    ("Cl2", 6.579, 0.05622),
+    ("CO", 1.505, 0.03985),
+    ("CO2", 3.640, 0.04267),
+    ("CH4", 2.283, 0.04278),
+    ("NH3", 4.225, 0.0371),
+    ("H2O", 5.536, 0.03049),
+    ("SO2", 6.803, 0.05636),
+];
+
+const NEWTON_ITERATIONS: usize = 100;
+
+/// Solve PV = nRT for whichever of pressure, volume (L), moles and
+/// temperature (K) is `None`.
+pub fn ideal_gas(
+    p: Option<f64>,
+    v: Option<f64>,
+    n: Option<f64>,
+    t: Option<f64>,
+    unit: PressureUnit,
+) -> Result<f64, StoichError> {
+    let r = unit.gas_constant();
+    let (numerator, divisor) = match (p, v, n, t) {
+        (None, Some(v), Some(n), Some(t)) => (n * r * t, v),
+        (Some(p), None, Some(n), Some(t)) => (n * r * t, p),
+        (Some(p), Some(v), None, Some(t)) => (p * v, r * t),
+        (Some(p), Some(v), Some(n), None) => (p * v, n * r),
+        _ => {
+            return Err(StoichError::InvalidInput(
+                "Exactly one of p, v, n and t must be unknown".to_string(),
+            ))
+        }
+    };
+    if divisor == 0.0 {
+        return Err(StoichError::InvalidInput(
+            "Cannot solve the ideal gas law with a zero quantity".to_string(),
+        ));
+    }
+    Ok(numerator / divisor)
+}
+
+/// The density in g/L of an ideal gas with the given molar mass (g/mol) at
+/// pressure `p` and temperature `t` (K).
+pub fn density(
+    molar_mass: f64,
+    p: f64,
+    t: f64,
+    unit: PressureUnit,
+) -> Result<f64, StoichError> {
+    let moles_per_liter = ideal_gas(Some(p), Some(1.0), None, Some(t), unit)?;
+    Ok(molar_mass * moles_per_liter)
+}
+
+/// The molar mass in g/mol of an ideal gas measured at `density` g/L, for
+/// identifying an unknown gas.
+pub fn molar_mass_from_density(
+    density: f64,
+    p: f64,
+    t: f64,
+    unit: PressureUnit,
+) -> Result<f64, StoichError> {
+    let moles_per_liter = ideal_gas(Some(p), Some(1.0), None, Some(t), unit)?;
+    if moles_per_liter == 0.0 {
+        return Err(StoichError::InvalidInput(
+            "Cannot find a molar mass at zero pressure".to_string(),
+        ));
+    }
+    Ok(density / moles_per_liter)
+}
+
+/// The van der Waals constants `(a, b)` of a common gas, with a in
+/// L²·unit/mol² and b in L/mol.
+pub fn van_der_waals_constants(
+    formula: &str,
+    unit: PressureUnit,
+) -> Option<(f64, f64)> {
+    let (species, _) = parse_state(formula);
+    VAN_DER_WAALS
+        .iter()
+        .find(|(gas, _, _)| *gas == species)
+        .map(|(_, a, b)| (a * unit.bar(), *b))
+}
+
+// Newton's method from `guess`, for the volume and moles forms of the van
+// der Waals equation, which are cubic.
+fn newton(
+    guess: f64,
+    f: impl Fn(f64) -> f64,
+    df: impl Fn(f64) -> f64,
+) -> Result<f64, StoichError> {
+    let mut x = guess;
+    for _ in 0..NEWTON_ITERATIONS {
+        let step = f(x) / df(x);
+        x -= step;
+        if !x.is_finite() {
+            break;
+        }
+        if step.abs() <= 1e-12 * x.abs() {
+            return Ok(x);
+        }
+    }
+    Err(StoichError::InvalidInput(
+        "Van der Waals equation did not converge".to_string(),
+    ))
+}
+
+/// Solve (P + an²/V²)(V - nb) = nRT for whichever of pressure, volume (L),
+/// moles and temperature (K) is `None`, with a in L²·unit/mol² and b in
+/// L/mol. Volume and moles take the gas-like root nearest the ideal one.
+pub fn van_der_waals(
+    p: Option<f64>,
+    v: Option<f64>,
+    n: Option<f64>,
+    t: Option<f64>,
+    (a, b): (f64, f64),
+    unit: PressureUnit,
+) -> Result<f64, StoichError> {
+    let r = unit.gas_constant();
+    let ideal = ideal_gas(p, v, n, t, unit)?;
+    let result = match (p, v, n, t) {
+        (None, Some(v), Some(n), Some(t)) => {
+            n * r * t / (v - n * b) - a * n * n / (v * v)
+        }
+        (Some(p), Some(v), Some(n), None) => {
+            (p + a * n * n / (v * v)) * (v - n * b) / (n * r)
+        }
+        (Some(p), None, Some(n), Some(t)) => newton(
+            ideal + n * b,
+            |v| (p + a * n * n / (v * v)) * (v - n * b) - n * r * t,
+            |v| p - a * n * n / (v * v) + 2.0 * a * b * n.powi(3) / v.powi(3),
+        )?,
+        (Some(p), Some(v), None, Some(t)) => newton(
+            ideal,
+            |n| (p + a * n * n / (v * v)) * (v - n * b) - n * r * t,
+            |n| {
+                2.0 * a * n / (v * v) * (v - n * b)
+                    - b * (p + a * n * n / (v * v))
+                    - r * t
+            },
+        )?,
+        _ => unreachable!("ideal_gas checks there is one unknown"),
+    };
+    if !result.is_finite() {
+        return Err(StoichError::InvalidInput(
+            "Van der Waals equation has no solution for these values"
+                .to_string(),
+        ));
+    }
+    Ok(result)
+}
+
+fn find_species<'a>(
+    reaction: &'a BalancedReaction,
+    formula: &str,
+) -> Option<&'a Reactant> {
+    let (species, _) = parse_state(formula);
+    reaction
+        .reactants
+        .iter()
+        .chain(reaction.products.iter())
+        .find(|r| parse_state(&r.compound.formula).0 == species)
+}
+
+/// Liters at STP of the gaseous `target` consumed or formed when `mass`
+/// grams of `reagent` react completely.
+pub fn stp_volume(
+    reaction: &BalancedReaction,
+    reagent: &str,
+    mass: f64,
+    target: &str,
+) -> Result<f64, StoichError> {
+    let supplied = reaction
+        .reactants
+        .iter()
+        .find(|r| parse_state(&r.compound.formula).0 == parse_state(reagent).0)
+        .ok_or_else(|| {
+            StoichError::InvalidInput(format!(
+                "{} is not a reagent in the reaction",
+                reagent
+            ))
+        })?;
+    let gas = find_species(reaction, target).ok_or_else(|| {
+        StoichError::InvalidInput(format!("{} is not in the reaction", target))
+    })?;
+    let gaseous = match gas.compound.state {
+        Some(state) => state == State::Gas,
+        None => GASES.contains(&gas.compound.formula.as_str()),
+    };
+    if !gaseous {
+        return Err(StoichError::InvalidInput(format!(
+            "{} is not a gas at STP",
+            gas.compound.formula
+        )));
+    }
+    let molrxn = mass
+        / supplied.compound.molar_mass()
+        / supplied.molar_coefficient as f64;
+    let moles = molrxn * gas.molar_coefficient as f64;
+    debug!(
+        "{} g of {} gives {} mol of {}",
+        mass, reagent, moles, target
+    );
+    Ok(moles * STP_MOLAR_VOLUME)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gas::{
+        density, ideal_gas, molar_mass_from_density, van_der_waals,
+        van_der_waals_constants, PressureUnit,
+    };
+    #[cfg(feature = "balance")]
+    use crate::{gas::stp_volume, parse::parse_equation, solve::balance};
+
+    #[test]
+    fn molar_volume_at_stp() {
+        let v = ideal_gas(
+            Some(1.0),
+            None,
+            Some(1.0),
+            Some(273.15),
+            PressureUnit::Atm,
+        )
+        .unwrap();
+        assert!((v - 22.414).abs() < 1e-2, "{}", v);
+        let v = ideal_gas(
+            Some(101.325),
+            None,
+            Some(1.0),
+            Some(273.15),
+            PressureUnit::KPa,
+        )
+        .unwrap();
+        assert!((v - 22.414).abs() < 1e-2, "{}", v);
+    }
+
+    #[test]
+    fn solve_each_quantity() {
+        let unit = PressureUnit::Atm;
+        let p = ideal_gas(None, Some(22.414), Some(1.0), Some(273.15), unit)
+            .unwrap();
+        assert!((p - 1.0).abs() < 1e-3, "{}", p);
+        let n = ideal_gas(Some(2.0), Some(22.414), None, Some(273.15), unit)
+            .unwrap();
+        assert!((n - 2.0).abs() < 1e-3, "{}", n);
+        let t =
+            ideal_gas(Some(1.0), Some(22.414), Some(1.0), None, unit).unwrap();
+        assert!((t - 273.15).abs() < 0.1, "{}", t);
+    }
+
+    #[test]
+    fn wrong_number_of_unknowns() {
+        let unit = PressureUnit::Atm;
+        assert!(ideal_gas(None, None, Some(1.0), Some(273.15), unit).is_err());
+        assert!(ideal_gas(Some(1.0), Some(1.0), Some(1.0), Some(1.0), unit)
+            .is_err());
+        assert!(ideal_gas(Some(1.0), Some(1.0), Some(0.0), None, unit).is_err());
+    }
+
+    #[test]
+    fn gas_density() {
+        let rho = density(44.01, 1.0, 273.15, PressureUnit::Atm).unwrap();
+        assert!((rho - 1.963).abs() < 1e-2, "{}", rho);
+        let m =
+            molar_mass_from_density(rho, 101.325, 273.15, PressureUnit::KPa)
+                .unwrap();
+        assert!((m - 44.01).abs() < 1e-2, "{}", m);
+        assert!(density(44.01, 1.0, 0.0, PressureUnit::Atm).is_err());
+        assert!(molar_mass_from_density(1.0, 0.0, 273.15, PressureUnit::Atm)
+            .is_err());
+    }
+
+    #[test]
+    fn van_der_waals_carbon_dioxide() {
+        let unit = PressureUnit::Atm;
+        let constants = van_der_waals_constants("CO2", unit).unwrap();
+        let p = van_der_waals(
+            None,
+            Some(1.0),
+            Some(1.0),
+            Some(300.0),
+            constants,
+            unit,
+        )
+        .unwrap();
+        assert!((p - 22.12).abs() < 0.01, "{}", p);
+        let v = van_der_waals(
+            Some(p),
+            None,
+            Some(1.0),
+            Some(300.0),
+            constants,
+            unit,
+        )
+        .unwrap();
+        assert!((v - 1.0).abs() < 1e-6, "{}", v);
+        let n = van_der_waals(
+            Some(p),
+            Some(1.0),
+            None,
+            Some(300.0),
+            constants,
+            unit,
+        )
+        .unwrap();
+        assert!((n - 1.0).abs() < 1e-6, "{}", n);
+        let t =
+            van_der_waals(Some(p), Some(1.0), Some(1.0), None, constants, unit)
+                .unwrap();
+        assert!((t - 300.0).abs() < 1e-6, "{}", t);
+    }
+
+    #[test]
+    fn van_der_waals_constant_lookup() {
+        let (a, b) =
+            van_der_waals_constants("N2(g)", PressureUnit::KPa).unwrap();
+        assert!((a - 137.0).abs() < 1e-9, "{}", a);
+        assert_eq!(b, 0.0387);
+        assert!(van_der_waals_constants("C8H18", PressureUnit::Atm).is_none());
+        let ideal = van_der_waals(
+            None,
+            Some(22.414),
+            Some(1.0),
+            Some(273.15),
+            (0.0, 0.0),
+            PressureUnit::Atm,
+        )
+        .unwrap();
+        assert!((ideal - 1.0).abs() < 1e-3, "{}", ideal);
+        assert!(van_der_waals(
+            None,
+            None,
+            Some(1.0),
+            Some(273.15),
+            (0.0, 0.0),
+            PressureUnit::Atm
+        )
+        .is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn oxygen_to_burn_propane() {
+        let (rg, pd) = parse_equation("C3H8 + O2 = CO2 + H2O").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        let v = stp_volume(&reaction, "C3H8", 44.1, "O2").unwrap();
+        assert!((v - 112.07).abs() < 0.1, "{}", v);
+        let v = stp_volume(&reaction, "C3H8", 44.1, "CO2").unwrap();
+        assert!((v - 67.24).abs() < 0.1, "{}", v);
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn non_gaseous_target() {
+        let (rg, pd) = parse_equation("C3H8 + O2 = CO2 + H2O(l)").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        let result = stp_volume(&reaction, "C3H8", 44.1, "H2O");
+        assert!(result.unwrap_err().to_string().contains("not a gas"));
+        let (rg, pd) = parse_equation("C3H8 + O2 = CO2 + H2O(g)").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        assert!(stp_volume(&reaction, "C3H8", 44.1, "H2O(g)").is_ok());
+        assert!(stp_volume(&reaction, "CO2", 44.1, "O2").is_err());
+    }
+}
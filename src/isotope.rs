@@ -0,0 +1,192 @@
+use crate::model::Element;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Isotope {
+    pub symbol: &'static str,
+    pub mass_number: u16,
+    /// Exact mass in daltons.
+    pub mass: f64,
+    /// Natural abundance as a fraction of the element's atoms.
+    pub abundance: f64,
+}
+
+// symbol, mass number, exact mass, natural abundance
+const ISOTOPES: [(&str, u16, f64, f64); 113] = [
+    ("H", 1, 1.007_825_032, 0.999_885),
+    ("H", 2, 2.014_101_778, 0.000_115),
+    ("H", 3, 3.016_049_278, 0.0),
+    ("He", 3, 3.016_029_319, 0.000_001_34),
+    ("He", 4, 4.002_603_254, 0.999_998_66),
+    ("Li", 6, 6.015_122_795, 0.0759),
+    ("Li", 7, 7.016_004_55, 0.9241),
+    ("Be", 9, 9.012_182_2, 1.0),
+    ("B", 10, 10.012_937, 0.199),
+    ("B", 11, 11.009_305_4, 0.801),
+    ("C", 12, 12.0, 0.9893),
+    ("C", 13, 13.003_354_838, 0.0107),
+    ("C", 14, 14.003_241_989, 0.0),
+    ("N", 14, 14.003_074_005, 0.996_36),
+    ("N", 15, 15.000_108_898, 0.003_64),
+    ("O", 16, 15.994_914_62, 0.997_57),
+    ("O", 17, 16.999_131_7, 0.000_38),
+    ("O", 18, 17.999_161, 0.002_05),
+    ("F", 19, 18.998_403_22, 1.0),
+    ("Ne", 20, 19.992_440_175, 0.9048),
+    ("Ne", 21, 20.993_846_68, 0.0027),
+    ("Ne", 22, 21.991_385_114, 0.0925),
+    ("Na", 23, 22.989_769_281, 1.0),
+    ("Mg", 24, 23.985_041_7, 0.7899),
+    ("Mg", 25, 24.985_836_92, 0.1),
+    ("Mg", 26, 25.982_592_929, 0.1101),
+    ("Al", 27, 26.981_538_63, 1.0),
+    ("Si", 28, 27.976_926_533, 0.922_23),
+    ("Si", 29, 28.976_494_7, 0.046_85),
+    ("Si", 30, 29.973_770_17, 0.030_92),
+    ("P", 31, 30.973_761_63, 1.0),
+    ("S", 32, 31.972_071, 0.9499),
+    ("S", 33, 32.971_458_76, 0.0075),
+    ("S", 34, 33.967_866_9, 0.0425),
+    ("S", 36, 35.967_080_76, 0.0001),
+    ("Cl", 35, 34.968_852_68, 0.7576),
+    ("Cl", 37, 36.965_902_59, 0.2424),
+    ("Ar", 36, 35.967_545_106, 0.003_365),
+    ("Ar", 38, 37.962_732_4, 0.000_632),
+    ("Ar", 40, 39.962_383_123, 0.996_003),
+    ("K", 39, 38.963_706_68, 0.932_581),
+    ("K", 40, 39.963_998_48, 0.000_117),
+    ("K", 41, 40.961_825_76, 0.067_302),
+    ("Ca", 40, 39.962_590_98, 0.969_41),
+    ("Ca", 42, 41.958_618_01, 0.006_47),
+    ("Ca", 43, 42.958_766_6, 0.001_35),
+    ("Ca", 44, 43.955_481_8, 0.020_86),
+    ("Ca", 46, 45.953_692_6, 0.000_04),
+    ("Ca", 48, 47.952_534, 0.001_87),
+    ("Cr", 50, 49.946_044_2, 0.043_45),
+    ("Cr", 52, 51.940_507_5, 0.837_89),
+    ("Cr", 53, 52.940_649_4, 0.095_01),
+    ("Cr", 54, 53.938_880_4, 0.023_65),
+    ("Mn", 55, 54.938_045_1, 1.0),
+    ("Fe", 54, 53.939_610_5, 0.058_45),
+    ("Fe", 56, 55.934_937_5, 0.917_54),
+    ("Fe", 57, 56.935_394, 0.021_19),
+    ("Fe", 58, 57.933_275_6, 0.002_82),
+    ("Co", 59, 58.933_195, 1.0),
+    ("Ni", 58, 57.935_342_9, 0.680_769),
+    ("Ni", 60, 59.930_786_4, 0.262_231),
+    ("Ni", 61, 60.931_056, 0.011_399),
+    ("Ni", 62, 61.928_345_1, 0.036_345),
+    ("Ni", 64, 63.927_966, 0.009_256),
+    ("Cu", 63, 62.929_597_5, 0.6915),
+    ("Cu", 65, 64.927_789_5, 0.3085),
+    ("Zn", 64, 63.929_142_2, 0.482_68),
+    ("Zn", 66, 65.926_033_4, 0.279_75),
+    ("Zn", 67, 66.927_127_3, 0.041_02),
+    ("Zn", 68, 67.924_844_2, 0.190_24),
+    ("Zn", 70, 69.925_319_3, 0.006_31),
+    ("Se", 74, 73.922_476_4, 0.0089),
+    ("Se", 76, 75.919_213_6, 0.0937),
+    ("Se", 77, 76.919_914, 0.0763),
+    ("Se", 78, 77.917_309_1, 0.2377),
+    ("Se", 80, 79.916_521_3, 0.4961),
+    ("Se", 82, 81.916_699_4, 0.0873),
+    ("Br", 79, 78.918_337_1, 0.5069),
+    ("Br", 81, 80.916_290_6, 0.4931),
+    ("Ag", 107, 106.905_097, 0.518_39),
+    ("Ag", 109, 108.904_752, 0.481_61),
+    ("Sn", 112, 111.904_818, 0.0097),
+    ("Sn", 114, 113.902_779, 0.0066),
+    ("Sn", 115, 114.903_342, 0.0034),
+    ("Sn", 116, 115.901_741, 0.1454),
+    ("Sn", 117, 116.902_952, 0.0768),
+    ("Sn", 118, 117.901_603, 0.2422),
+    ("Sn", 119, 118.903_308, 0.0859),
+    ("Sn", 120, 119.902_194_7, 0.3258),
+    ("Sn", 122, 121.903_439, 0.0463),
+    ("Sn", 124, 123.905_273_9, 0.0579),
+    ("I", 127, 126.904_473, 1.0),
+    ("Pt", 190, 189.959_932, 0.000_12),
+    ("Pt", 192, 191.961_038, 0.007_82),
+    ("Pt", 194, 193.962_680_3, 0.3286),
+    ("Pt", 195, 194.964_791_1, 0.3378),
+    ("Pt", 196, 195.964_951_5, 0.2521),
+    ("Pt", 198, 197.967_893, 0.073_56),
+    ("Au", 197, 196.966_568_7, 1.0),
+    ("Hg", 196, 195.965_833, 0.0015),
+    ("Hg", 198, 197.966_769, 0.0997),
+    ("Hg", 199, 198.968_279_9, 0.1687),
+    ("Hg", 200, 199.968_326, 0.231),
+    ("Hg", 201, 200.970_302_3, 0.1318),
+    ("Hg", 202, 201.970_643, 0.2986),
+    ("Hg", 204, 203.973_493_9, 0.0687),
+    ("Pb", 204, 203.973_043_6, 0.014),
+    ("Pb", 206, 205.974_465_3, 0.241),
+    ("Pb", 207, 206.975_896_9, 0.221),
+    ("Pb", 208, 207.976_652_1, 0.524),
+    ("U", 234, 234.040_952_1, 0.000_054),
+    ("U", 235, 235.043_929_9, 0.007_204),
+    ("U", 238, 238.050_788_2, 0.992_742),
+];
+
+/// The tabulated isotopes of `element`, lightest first.
+pub fn isotopes(element: &Element) -> Vec<Isotope> {
+    ISOTOPES
+        .iter()
+        .filter(|(symbol, ..)| *symbol == element.get_symbol())
+        .map(|(symbol, mass_number, mass, abundance)| Isotope {
+            symbol,
+            mass_number: *mass_number,
+            mass: *mass,
+            abundance: *abundance,
+        })
+        .collect()
+}
+
+pub fn isotope(element: &Element, mass_number: u16) -> Option<Isotope> {
+    isotopes(element)
+        .into_iter()
+        .find(|i| i.mass_number == mass_number)
+}
+
+/// The most abundant isotope of `element`.
+pub fn principal_isotope(element: &Element) -> Option<Isotope> {
+    isotopes(element).into_iter().max_by(|a, b| {
+        a.abundance
+            .partial_cmp(&b.abundance)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::isotope::{isotope, principal_isotope, ISOTOPES};
+    use crate::model::Element;
+
+    #[test]
+    fn abundances_sum_to_one() {
+        let mut totals: HashMap<&str, f64> = HashMap::new();
+        for (symbol, _, _, abundance) in ISOTOPES.iter() {
+            *totals.entry(symbol).or_insert(0.0) += abundance;
+        }
+        for (symbol, total) in totals {
+            assert!((total - 1.0).abs() < 1e-6, "{} sums to {}", symbol, total);
+        }
+        let lead = Element::from_symbol("Pb").unwrap();
+        assert!(isotope(&lead, 204).is_some());
+        assert!(isotope(&lead, 206).is_some());
+        assert!(isotope(&lead, 207).is_some());
+    }
+
+    #[test]
+    fn principal_isotopes() {
+        let carbon = Element::from_symbol("C").unwrap();
+        assert_eq!(principal_isotope(&carbon).unwrap().mass_number, 12);
+        let chlorine = Element::from_symbol("Cl").unwrap();
+        assert_eq!(principal_isotope(&chlorine).unwrap().mass_number, 35);
+        let xenon = Element::from_symbol("Xe").unwrap();
+        assert!(principal_isotope(&xenon).is_none());
+        assert!(isotope(&carbon, 13).is_some());
+        assert!(isotope(&carbon, 15).is_none());
+    }
+}
@@ -2,10 +2,23 @@ extern crate env_logger;
 #[macro_use]
 extern crate log;
 
+pub mod bio;
+pub mod colligative;
+pub mod error;
 pub mod ext;
+pub mod gas;
+pub mod isotope;
+pub mod mixture;
 pub mod model;
 pub mod molecule;
 pub mod parse;
+pub mod ph;
+pub mod prelude;
+pub mod sigfig;
+pub mod solution;
+#[cfg(feature = "balance")]
 pub mod solve;
+pub mod thermo;
+pub mod titration;
 
 mod test_utils;
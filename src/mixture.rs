@@ -0,0 +1,83 @@
+use crate::error::StoichError;
+use crate::model::{Compound, Substance};
+
+fn fractions(amounts: Vec<f64>) -> Result<Vec<f64>, StoichError> {
+    if amounts.is_empty() {
+        return Err(StoichError::InvalidInput("Mixture is empty".to_string()));
+    }
+    let total: f64 = amounts.iter().sum();
+    if total <= 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Mixture total must be positive, got {}",
+            total
+        )));
+    }
+    Ok(amounts.iter().map(|amount| amount / total).collect())
+}
+
+/// Each component's share of the total moles, in the order given.
+pub fn mole_fractions(mixture: &[Substance]) -> Result<Vec<f64>, StoichError> {
+    fractions(mixture.iter().map(|s| s.moles()).collect())
+}
+
+/// Each component's share of the total mass, in the order given.
+pub fn mass_fractions(mixture: &[Substance]) -> Result<Vec<f64>, StoichError> {
+    fractions(mixture.iter().map(|s| s.mass()).collect())
+}
+
+/// The number-averaged molar mass of compounds given with their moles.
+pub fn average_molar_mass(
+    mixture: &[(Compound, f64)],
+) -> Result<f64, StoichError> {
+    if let Some((compound, moles)) = mixture.iter().find(|(_, n)| *n < 0.0) {
+        return Err(StoichError::InvalidInput(format!(
+            "Moles of {} must not be negative, got {}",
+            compound.formula, moles
+        )));
+    }
+    let fractions = fractions(mixture.iter().map(|(_, n)| *n).collect())?;
+    Ok(mixture
+        .iter()
+        .zip(fractions)
+        .map(|((compound, _), x)| x * compound.molar_mass())
+        .sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mixture::{average_molar_mass, mass_fractions, mole_fractions};
+    use crate::model::{Compound, Substance};
+
+    #[test]
+    fn water_ethanol() {
+        let mixture = vec![
+            Substance::from_formula("H2O", 18.015, 1).unwrap(),
+            Substance::from_formula("C2H5OH", 46.07, 1).unwrap(),
+        ];
+        let moles = mole_fractions(&mixture).unwrap();
+        assert!((moles[0] - 0.5).abs() < 1e-3, "{:?}", moles);
+        assert!((moles.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let masses = mass_fractions(&mixture).unwrap();
+        assert!((masses[0] - 0.2811).abs() < 1e-3, "{:?}", masses);
+        assert!((masses.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn empty_mixture() {
+        assert!(mole_fractions(&[]).is_err());
+        assert!(mass_fractions(&[]).is_err());
+    }
+
+    #[test]
+    fn air_molar_mass() {
+        let air = vec![
+            (Compound::from_formula("N2").unwrap(), 0.79),
+            (Compound::from_formula("O2").unwrap(), 0.21),
+        ];
+        let mass = average_molar_mass(&air).unwrap();
+        assert!((mass - 28.85).abs() < 0.01, "{}", mass);
+        assert!(average_molar_mass(&[]).is_err());
+        let none = vec![(Compound::from_formula("N2").unwrap(), 0.0)];
+        assert!(average_molar_mass(&none).is_err());
+    }
+}
@@ -1,36 +1,303 @@
 use std::cmp::{Eq, Ordering};
-use std::collections::HashMap;
-use std::{hash::Hash, iter::FromIterator};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+use std::str::FromStr;
 
-use periodic_table_on_an_enum::Element as PElement;
+use periodic_table_on_an_enum::{Element as PElement, GroupBlock};
+#[cfg(feature = "balance")]
+use rug::Rational;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::molecule::molecular_weight;
-use crate::parse::parse_formula;
+use crate::error::StoichError;
+use crate::isotope::isotope;
+use crate::molecule::{
+    hill_order, ion_mass, molecular_weight, monoisotopic_mass,
+};
+use crate::parse::{
+    expand_abbreviations, parse_charge, parse_formula, parse_state, SUBSCRIPTS,
+};
 
+pub const ELECTRON_MOLAR_MASS: f64 = 0.000_548_58;
+/// Particles per mole, exact since the 2019 SI redefinition.
+pub const AVOGADRO: f64 = 6.022_140_76e23;
+// Relative difference allowed between the masses on each side.
+const MASS_TOLERANCE: f64 = 1e-9;
+
+/// Deserializing recomputes the molar mass from `atoms` and `charge`, with
+/// the electron correction on.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "CompoundFields"))]
 pub struct Compound {
     pub formula: String,
-    pub atoms: HashMap<Element, u32>,
-    molar_mass: f32,
+    pub atoms: BTreeMap<Element, u32>,
+    pub charge: i32,
+    pub state: Option<State>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    molar_mass: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    electron_correction: bool,
+}
+
+// The serialized fields of a `Compound`, without the cached mass.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct CompoundFields {
+    formula: String,
+    atoms: BTreeMap<Element, u32>,
+    charge: i32,
+    state: Option<State>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum State {
+    Solid,
+    Liquid,
+    Gas,
+    Aqueous,
+}
+
+impl State {
+    pub const ALL: [State; 4] =
+        [State::Solid, State::Liquid, State::Gas, State::Aqueous];
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            State::Solid => "(s)",
+            State::Liquid => "(l)",
+            State::Gas => "(g)",
+            State::Aqueous => "(aq)",
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
 }
 
 impl Compound {
-    pub fn from_formula(formula: &str) -> Result<Compound, String> {
+    pub fn from_formula(formula: &str) -> Result<Compound, StoichError> {
         Compound::new(formula)
     }
 
-    pub fn new(formula: &str) -> Result<Compound, String> {
-        let atoms = parse_formula(formula);
-        let molecular_weight = atoms.clone().and_then(molecular_weight)?;
+    /// Like `from_formula`, but with organic abbreviations such as `Me`,
+    /// `Et` and `Ph` expanded when `abbreviations` is set. `formula` keeps
+    /// the shorthand as written.
+    pub fn from_formula_with(
+        formula: &str,
+        abbreviations: bool,
+    ) -> Result<Compound, StoichError> {
+        if !abbreviations {
+            return Compound::new(formula);
+        }
+        let mut compound = Compound::new(&expand_abbreviations(formula))?;
+        compound.formula = formula.to_string();
+        Ok(compound)
+    }
+
+    /// A trailing state symbol such as `(aq)` is kept in `formula` and
+    /// stored in `state`, but does not count towards the atoms.
+    pub fn new(formula: &str) -> Result<Compound, StoichError> {
+        let (species, state) = parse_state(formula);
+        let (_, charge) = parse_charge(species)?;
+        let atoms = parse_formula(species)?;
+        let molecular_weight = molecular_weight(atoms.clone())?;
         Ok(Compound {
             formula: formula.to_string(),
-            atoms: atoms?,
-            molar_mass: molecular_weight,
+            atoms,
+            charge,
+            state,
+            molar_mass: ion_mass(molecular_weight, charge),
+            electron_correction: true,
         })
     }
+
+    /// Grams per mole. For ions this includes the mass of the electrons
+    /// gained or lost, unless the correction has been turned off.
+    pub fn molar_mass(&self) -> f64 {
+        self.molar_mass
+    }
+
+    /// The sum of the atomic weights, without the electron correction that
+    /// `molar_mass` applies to ions.
+    pub fn molecular_weight(&self) -> Result<f64, StoichError> {
+        molecular_weight(self.atoms.clone())
+    }
+
+    /// Turn the electron mass correction for charged species on or off.
+    pub fn set_electron_correction(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), StoichError> {
+        if self.atoms.is_empty() {
+            return Ok(());
+        }
+        let neutral = self.molecular_weight()?;
+        self.molar_mass = match enabled {
+            true => ion_mass(neutral, self.charge),
+            false => neutral,
+        };
+        self.electron_correction = enabled;
+        Ok(())
+    }
+
+    /// The monoisotopic mass, corrected for electrons like `molar_mass`.
+    pub fn monoisotopic_mass(&self) -> Result<f64, StoichError> {
+        let neutral = monoisotopic_mass(&self.atoms)?;
+        Ok(match self.electron_correction {
+            true => ion_mass(neutral, self.charge),
+            false => neutral,
+        })
+    }
+
+    /// Each element with its count, sorted by symbol and then mass number.
+    pub fn elements(&self) -> impl Iterator<Item = (&Element, u32)> {
+        self.atoms.iter().map(|(e, count)| (e, *count))
+    }
+
+    /// The formula in Hill notation, e.g. `C2H6O` for ethanol and `H2O4S`
+    /// for sulfuric acid. Charge and state are left out.
+    pub fn hill_formula(&self) -> String {
+        hill_order(self.elements())
+    }
+
+    /// The Hill formula followed by any charge and state, e.g. `HNaO` for
+    /// both `NaOH` and `HONa`. Compounds compare and hash by this key.
+    pub fn canonical_key(&self) -> String {
+        let charge = match self.charge {
+            0 => String::new(),
+            1 => "+".to_string(),
+            -1 => "-".to_string(),
+            c if c > 0 => format!("^{}+", c),
+            c => format!("^{}-", -c),
+        };
+        let state = self.state.map_or("", |s| s.symbol());
+        format!("{}{}{}", self.hill_formula(), charge, state)
+    }
+
+    // Greatest common divisor of the atom counts, 0 when there are none.
+    fn atom_gcd(&self) -> u32 {
+        let gcd = |mut a: u32, mut b: u32| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        self.atoms.values().fold(0, |acc, count| gcd(acc, *count))
+    }
+
+    /// Whether the atom counts share no factor greater than 1, as in `CH2O`
+    /// but not `C6H12O6`.
+    pub fn is_empirical(&self) -> bool {
+        self.atom_gcd() <= 1
+    }
+
+    /// The neutral compound with the atom counts divided by their greatest
+    /// common divisor, in Hill notation: `CH2O` for `C6H12O6`.
+    pub fn empirical_form(&self) -> Compound {
+        let divisor = self.atom_gcd().max(1);
+        let atoms: BTreeMap<Element, u32> = self
+            .atoms
+            .iter()
+            .map(|(element, count)| (*element, count / divisor))
+            .collect();
+        Compound {
+            formula: hill_order(atoms.iter().map(|(e, count)| (e, *count))),
+            molar_mass: atoms
+                .iter()
+                .map(|(e, count)| e.get_atomic_mass() * *count as f64)
+                .sum(),
+            atoms,
+            charge: 0,
+            state: None,
+            electron_correction: true,
+        }
+    }
+
+    /// Mass percentage of each element in the compound, out of the summed
+    /// atomic weights so that an ion's electrons do not count.
+    pub fn percent_composition(&self) -> HashMap<Element, f64> {
+        let masses: Vec<(Element, f64)> = self
+            .atoms
+            .iter()
+            .map(|(element, count)| {
+                (*element, element.get_atomic_mass() * *count as f64)
+            })
+            .collect();
+        let total: f64 = masses.iter().map(|(_, mass)| mass).sum();
+        masses
+            .into_iter()
+            .map(|(element, mass)| (element, mass / total * 100.0))
+            .collect()
+    }
+
+    /// The formula with atom counts as Unicode subscripts, e.g. `Al₂O₃`.
+    /// Hydrate multipliers, charges and states are left as written.
+    pub fn to_unicode_string(&self) -> String {
+        let (species, _) = parse_state(&self.formula);
+        let body =
+            parse_charge(species).map_or(species.len(), |(body, _)| body.len());
+        let mut unicode = String::new();
+        let mut prev: Option<char> = None;
+        for c in self.formula[..body].chars() {
+            let count = prev.is_some_and(|p| {
+                p.is_alphabetic()
+                    || ")]}".contains(p)
+                    || SUBSCRIPTS.contains(&p)
+            });
+            let c = match c.to_digit(10) {
+                Some(d) if count => SUBSCRIPTS[d as usize],
+                _ => c,
+            };
+            unicode.push(c);
+            prev = Some(c);
+        }
+        unicode + &self.formula[body..]
+    }
+
+    pub fn electron() -> Compound {
+        Compound {
+            formula: "e-".to_string(),
+            atoms: BTreeMap::new(),
+            charge: -1,
+            state: None,
+            molar_mass: ELECTRON_MOLAR_MASS,
+            electron_correction: true,
+        }
+    }
+}
+
+impl PartialEq for Compound {
+    fn eq(&self, other: &Self) -> bool {
+        self.canonical_key() == other.canonical_key()
+    }
+}
+
+impl Eq for Compound {}
+
+impl Hash for Compound {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_key().hash(state);
+    }
+}
+
+impl FromStr for Compound {
+    type Err = StoichError;
+
+    fn from_str(formula: &str) -> Result<Self, Self::Err> {
+        Compound::from_formula(formula)
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Reactant {
     pub compound: Compound,
     pub molar_coefficient: u32,
@@ -44,31 +311,80 @@ impl Reactant {
         }
     }
 
-    pub fn from_formula(formula: &str, coeff: u32) -> Result<Self, String> {
+    pub fn from_formula(
+        formula: &str,
+        coeff: u32,
+    ) -> Result<Self, StoichError> {
         let cmp = Compound::from_formula(formula);
         Ok(Reactant {
             compound: cmp?,
             molar_coefficient: coeff,
         })
     }
+
+    /// Grams per mole of reaction: the coefficient times the molecular
+    /// weight.
+    pub fn mass_contribution(&self) -> Result<f64, StoichError> {
+        Ok(self.molar_coefficient as f64 * self.compound.molecular_weight()?)
+    }
+}
+
+/// Parses a formula with an optional coefficient separated by whitespace,
+/// e.g. `2 H2O`.
+impl FromStr for Reactant {
+    type Err = StoichError;
+
+    fn from_str(term: &str) -> Result<Self, Self::Err> {
+        let term = term.trim();
+        let (coeff, formula) = match term.split_once(char::is_whitespace) {
+            Some((coeff, formula))
+                if coeff.chars().all(|c| c.is_ascii_digit()) =>
+            {
+                let coeff = coeff.parse::<u32>().map_err(|e| {
+                    StoichError::parse(
+                        0,
+                        format!("Invalid coefficient {:?}: {}", coeff, e),
+                    )
+                })?;
+                (coeff, formula.trim_start())
+            }
+            _ => (1, term),
+        };
+        if coeff == 0 {
+            return Err(StoichError::parse(
+                0,
+                format!("Coefficient of {:?} must be positive", term),
+            ));
+        }
+        let offset = term.len() - formula.len();
+        let compound =
+            Compound::from_formula(formula).map_err(|e| match e {
+                StoichError::ParseError { position, msg } => {
+                    StoichError::parse(offset + position, msg)
+                }
+                e => e,
+            })?;
+        Ok(Reactant::of_compound(compound, coeff))
+    }
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Substance {
     pub reactant: Reactant,
-    pub mass: f32,
+    pub mass: f64,
 }
 
 impl Substance {
-    pub fn of_reactant(reactant: Reactant, mass: f32) -> Self {
+    pub fn of_reactant(reactant: Reactant, mass: f64) -> Self {
         Substance { reactant, mass }
     }
 
     pub fn from_formula(
         formula: &str,
-        mass: f32,
+        mass: f64,
         molar_coefficient: u32,
-    ) -> Result<Substance, String> {
+    ) -> Result<Substance, StoichError> {
         let rct = Reactant::from_formula(formula, molar_coefficient);
         Ok(Substance {
             reactant: rct?,
@@ -76,18 +392,71 @@ impl Substance {
         })
     }
 
-    pub fn moles(self: &Self) -> f32 {
+    /// The substance holding `moles` of `formula`, with its mass worked out
+    /// from the molecular weight.
+    pub fn from_moles(
+        formula: &str,
+        moles: f64,
+    ) -> Result<Substance, StoichError> {
+        let reactant = Reactant::from_formula(formula, 1)?;
+        if reactant.compound.molar_mass <= 0.0 {
+            return Err(StoichError::InvalidInput(format!(
+                "{} has no molecular weight",
+                formula
+            )));
+        }
+        let mass = moles * reactant.compound.molar_mass;
+        Ok(Substance { reactant, mass })
+    }
+
+    pub fn from_particles(
+        formula: &str,
+        count: f64,
+    ) -> Result<Substance, StoichError> {
+        Substance::from_moles(formula, count / AVOGADRO)
+    }
+
+    pub fn mass(&self) -> f64 {
+        self.mass
+    }
+
+    /// Number of molecules (or formula units) in the substance.
+    pub fn particles(&self) -> f64 {
+        self.moles() * AVOGADRO
+    }
+
+    pub fn moles(&self) -> f64 {
         self.mass / self.reactant.compound.molar_mass
     }
 
-    pub fn molrxn(self: &Self) -> f32 {
-        self.moles() / self.reactant.molar_coefficient as f32
+    pub fn molrxn(&self) -> f64 {
+        self.moles() / self.reactant.molar_coefficient as f64
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "String", into = "String")
+)]
 pub struct Element {
     element: PElement,
+    mass_number: Option<u16>,
+}
+
+// Elements sort by symbol, with unlabelled atoms before their isotopes.
+impl Ord for Element {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.get_symbol(), self.mass_number)
+            .cmp(&(other.get_symbol(), other.mass_number))
+    }
+}
+
+impl PartialOrd for Element {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 impl Element {
@@ -100,21 +469,127 @@ impl Element {
     }
 
     fn from_pt_element(element: PElement) -> Element {
-        Element { element }
+        Element {
+            element,
+            mass_number: None,
+        }
+    }
+
+    /// The same element labelled as the isotope with `mass_number`, if that
+    /// isotope is tabulated.
+    pub fn isotope(&self, mass_number: u16) -> Option<Element> {
+        isotope(self, mass_number).map(|_| Element {
+            element: self.element,
+            mass_number: Some(mass_number),
+        })
+    }
+
+    pub fn get_mass_number(&self) -> Option<u16> {
+        self.mass_number
+    }
+
+    /// Standard atomic weight, or the exact isotope mass when labelled.
+    pub fn get_atomic_mass(&self) -> f64 {
+        match self.mass_number.and_then(|n| isotope(self, n)) {
+            Some(isotope) => isotope.mass,
+            // Widen through the shortest decimal so that a table value of
+            // 1.008 stays 1.008 instead of picking up f32 rounding error.
+            None => self
+                .element
+                .get_atomic_mass()
+                .to_string()
+                .parse()
+                .unwrap_or_else(|_| self.element.get_atomic_mass() as f64),
+        }
+    }
+
+    /// The symbol with any isotope label, e.g. `^13C`.
+    pub fn notation(&self) -> String {
+        match self.mass_number {
+            Some(n) => format!("^{}{}", n, self.get_symbol()),
+            None => self.get_symbol().to_string(),
+        }
+    }
+
+    pub fn get_symbol(&self) -> &'static str {
+        self.element.get_symbol()
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        self.element.get_name()
+    }
+
+    pub fn get_atomic_number(&self) -> u8 {
+        self.element.get_atomic_number() as u8
+    }
+
+    pub fn get_oxidation_states(&self) -> &'static [i8] {
+        self.element.get_oxidation_states()
+    }
+
+    pub fn is_metal(&self) -> bool {
+        !matches!(
+            self.element.get_group(),
+            GroupBlock::Metalloid
+                | GroupBlock::NonMetal
+                | GroupBlock::Halogen
+                | GroupBlock::NobleGas
+        )
+    }
+
+    pub fn from_name(name: &str) -> Option<Element> {
+        PElement::from_name_case_insensitive(name).map(Element::from_pt_element)
     }
+}
+
+impl TryFrom<String> for Element {
+    type Error = StoichError;
+
+    fn try_from(symbol: String) -> Result<Self, Self::Error> {
+        match parse_formula(&symbol)?.into_iter().collect::<Vec<_>>()[..] {
+            [(element, 1)] => Ok(element),
+            _ => Err(StoichError::UnknownElement(symbol)),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<CompoundFields> for Compound {
+    type Error = StoichError;
+
+    fn try_from(fields: CompoundFields) -> Result<Self, Self::Error> {
+        let molecular_weight = molecular_weight(fields.atoms.clone())?;
+        Ok(Compound {
+            formula: fields.formula,
+            atoms: fields.atoms,
+            charge: fields.charge,
+            state: fields.state,
+            molar_mass: ion_mass(molecular_weight, fields.charge),
+            electron_correction: true,
+        })
+    }
+}
 
-    pub fn get_atomic_mass(&self) -> f32 {
-        self.element.get_atomic_mass()
+impl From<Element> for String {
+    fn from(element: Element) -> String {
+        element.notation()
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BalancedReaction {
+    #[cfg_attr(feature = "serde", serde(rename = "reagents"))]
     pub reactants: Vec<Reactant>,
     pub products: Vec<Reactant>,
+    /// Written with an equilibrium arrow, `<=>`, rather than `->`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub reversible: bool,
 }
 
-struct ReactantMap(HashMap<String, u32>);
+// Counts each (formula, coefficient) pair, so that sides compare as
+// multisets regardless of order.
+struct ReactantMap(HashMap<(String, u32), usize>);
 
 impl FromIterator<Reactant> for ReactantMap {
     fn from_iter<R>(reactants: R) -> Self
@@ -123,7 +598,8 @@ impl FromIterator<Reactant> for ReactantMap {
     {
         let mut m = HashMap::new();
         for r in reactants {
-            m.entry(r.compound.formula).or_insert(r.molar_coefficient);
+            *m.entry((r.compound.formula, r.molar_coefficient))
+                .or_insert(0) += 1;
         }
         Self(m)
     }
@@ -135,11 +611,7 @@ impl PartialEq for BalancedReaction {
         let ReactantMap(p) = self.products.clone().into_iter().collect();
         let ReactantMap(or) = other.reactants.clone().into_iter().collect();
         let ReactantMap(op) = other.products.clone().into_iter().collect();
-        r == or && p == op
-    }
-
-    fn ne(&self, other: &Self) -> bool {
-        !self.eq(other)
+        r == or && p == op && self.reversible == other.reversible
     }
 }
 
@@ -148,48 +620,466 @@ impl BalancedReaction {
         BalancedReaction {
             reactants,
             products,
+            reversible: false,
+        }
+    }
+
+    fn arrow(&self) -> &'static str {
+        match self.reversible {
+            true => "<=>",
+            false => "->",
         }
     }
 
-    fn reactants_display_string(&self) -> String {
-        let balr: Vec<String> = self
+    // Both sides joined by `arrow`. Coefficients of 1 are written only with
+    // `ones`, and `unicode` gives formulas subscripts.
+    fn format_sides(&self, arrow: &str, unicode: bool, ones: bool) -> String {
+        let side = |reactants: &[Reactant]| -> String {
+            reactants
+                .iter()
+                .map(|r| {
+                    let formula = match unicode {
+                        true => r.compound.to_unicode_string(),
+                        false => r.compound.formula.clone(),
+                    };
+                    match r.molar_coefficient {
+                        1 if !ones => formula,
+                        n => format!("{} {}", n, formula),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" + ")
+        };
+        format!(
+            "{} {} {}",
+            side(&self.reactants),
+            arrow,
+            side(&self.products)
+        )
+    }
+
+    /// The form the command line prints, e.g. `2 Al + 3 Cl2 = 2 AlCl3`:
+    /// every coefficient is written, 1 included, and the sides are split by
+    /// `=` (`<=>` when reversible), the separator the CLI takes as input.
+    /// `Display` is the same reaction written for reading, with `->` and
+    /// without coefficients of 1.
+    pub fn display_string(&self) -> String {
+        let arrow = match self.reversible {
+            true => "<=>",
+            false => "=",
+        };
+        self.format_sides(arrow, false, true)
+    }
+
+    /// The equilibrium constant expression, e.g. `Kc = [NH3]^2 / [N2][H2]^3`.
+    /// Pure solids and liquids, marked `(s)` or `(l)`, are left out.
+    pub fn equilibrium_expression(&self) -> String {
+        let terms = |side: &[Reactant]| -> Vec<String> {
+            side.iter()
+                .filter(|r| {
+                    !matches!(
+                        r.compound.state,
+                        Some(State::Solid | State::Liquid)
+                    )
+                })
+                .map(|r| {
+                    let (species, _) = parse_state(&r.compound.formula);
+                    match r.molar_coefficient {
+                        1 => format!("[{}]", species),
+                        n => format!("[{}]^{}", species, n),
+                    }
+                })
+                .collect()
+        };
+        let numerator = terms(&self.products);
+        let denominator = terms(&self.reactants);
+        let numerator = match numerator.is_empty() {
+            true => "1".to_string(),
+            false => numerator.concat(),
+        };
+        match denominator.is_empty() {
+            true => format!("Kc = {}", numerator),
+            false => format!("Kc = {} / {}", numerator, denominator.concat()),
+        }
+    }
+
+    /// Whether both sides carry the same atoms and the same net charge.
+    pub fn is_balanced(&self) -> bool {
+        let totals = |side: &[Reactant]| -> (HashMap<Element, u64>, i64) {
+            let mut atoms = HashMap::new();
+            let mut charge = 0;
+            for r in side {
+                for (e, count) in &r.compound.atoms {
+                    *atoms.entry(*e).or_insert(0) +=
+                        *count as u64 * r.molar_coefficient as u64;
+                }
+                charge += r.compound.charge as i64 * r.molar_coefficient as i64;
+            }
+            (atoms, charge)
+        };
+        totals(&self.reactants) == totals(&self.products)
+    }
+
+    /// Check that the reactants and products weigh the same, as a sanity
+    /// check on the coefficients.
+    pub fn verify_mass_conservation(&self) -> Result<(), StoichError> {
+        let mass = |side: &[Reactant]| -> Result<f64, StoichError> {
+            side.iter().map(|r| r.mass_contribution()).sum()
+        };
+        let (reactants, products) =
+            (mass(&self.reactants)?, mass(&self.products)?);
+        let scale = reactants.abs().max(products.abs()).max(1.0);
+        match (reactants - products).abs() <= MASS_TOLERANCE * scale {
+            true => Ok(()),
+            false => Err(StoichError::InvalidInput(format!(
+                "Reactants weigh {} g but products weigh {} g in {}",
+                reactants, products, self
+            ))),
+        }
+    }
+
+    fn species(&self, formula: &str) -> Result<&Reactant, StoichError> {
+        self.reactants
+            .iter()
+            .chain(self.products.iter())
+            .find(|r| r.compound.formula == formula)
+            .ok_or_else(|| {
+                StoichError::InvalidInput(format!(
+                    "{} is not in the reaction",
+                    formula
+                ))
+            })
+    }
+
+    /// Moles of `from` per mole of `to`: for `2 H2 + O2 -> 2 H2O`,
+    /// `mole_ratio("O2", "H2O")` is 1/2.
+    #[cfg(feature = "balance")]
+    pub fn mole_ratio(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Rational, StoichError> {
+        let from = self.species(from)?;
+        let to = self.species(to)?;
+        Ok(Rational::from((
+            from.molar_coefficient,
+            to.molar_coefficient,
+        )))
+    }
+
+    /// Grams of `to` formed or consumed alongside `from_mass` grams of
+    /// `from`, assuming the reaction goes to completion.
+    pub fn mass_to_mass(
+        &self,
+        from: &str,
+        from_mass: f64,
+        to: &str,
+    ) -> Result<f64, StoichError> {
+        let (source, target) = (self.species(from)?, self.species(to)?);
+        let from_moles = from_mass / source.compound.molar_mass;
+        let to_moles = from_moles * target.molar_coefficient as f64
+            / source.molar_coefficient as f64;
+        debug!(
+            "{} moles of {} give {} moles of {}",
+            from_moles, from, to_moles, to
+        );
+        Ok(to_moles * target.compound.molar_mass)
+    }
+
+    /// The reaction as an mhchem expression, e.g.
+    /// `\ce{2 Al + 3 Cl2 -> 2 AlCl3}`.
+    pub fn to_latex(&self) -> String {
+        let side = |reactants: &[Reactant]| -> String {
+            reactants
+                .iter()
+                .map(|r| {
+                    let formula = mhchem_formula(&r.compound.formula);
+                    match r.molar_coefficient {
+                        1 => formula,
+                        n => format!("{} {}", n, formula),
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" + ")
+        };
+        format!(
+            "\\ce{{{} {} {}}}",
+            side(&self.reactants),
+            self.arrow(),
+            side(&self.products)
+        )
+    }
+
+    /// Find the reagent that runs out first given the grams supplied of
+    /// each reagent formula, along with the moles of reaction it allows.
+    pub fn limiting_reagent(
+        &self,
+        masses: &HashMap<String, f64>,
+    ) -> Result<(&Reactant, f64), StoichError> {
+        if let Some(unknown) = masses
+            .keys()
+            .find(|f| !self.reactants.iter().any(|r| &r.compound.formula == *f))
+        {
+            return Err(StoichError::InvalidInput(format!(
+                "{} is not a reagent in the reaction",
+                unknown
+            )));
+        }
+        let mut limiting: Option<(&Reactant, f64)> = None;
+        for reactant in &self.reactants {
+            let formula = &reactant.compound.formula;
+            let mass = masses.get(formula).ok_or_else(|| {
+                StoichError::InvalidInput(format!(
+                    "No mass supplied for {}",
+                    formula
+                ))
+            })?;
+            let molrxn =
+                Substance::of_reactant(reactant.clone(), *mass).molrxn();
+            trace!("{} supplies {} moles of reaction", formula, molrxn);
+            if limiting.is_none_or(|(_, least)| molrxn < least) {
+                limiting = Some((reactant, molrxn));
+            }
+        }
+        let (reactant, molrxn) = limiting.ok_or_else(|| {
+            StoichError::InvalidInput("Reaction has no reagents".to_string())
+        })?;
+        debug!("Limiting reagent is {}", reactant.compound.formula);
+        Ok((reactant, molrxn))
+    }
+
+    /// Grams of each reagent left over once the limiting reagent has been
+    /// used up, keyed by formula. The limiting reagent is left with zero.
+    pub fn excess_reagents(
+        &self,
+        masses: &HashMap<String, f64>,
+    ) -> Result<HashMap<String, f64>, StoichError> {
+        let (limiting, molrxn) = self.limiting_reagent(masses)?;
+        Ok(self
             .reactants
             .iter()
-            .map(|r| format!("{} {}", r.molar_coefficient, r.compound.formula))
-            .collect();
-        balr.join(" + ")
+            .map(|r| {
+                let formula = r.compound.formula.clone();
+                let remaining =
+                    match r.compound.formula == limiting.compound.formula {
+                        true => 0.0,
+                        false => {
+                            let consumed = molrxn
+                                * r.molar_coefficient as f64
+                                * r.compound.molar_mass;
+                            trace!("{} g of {} consumed", consumed, formula);
+                            masses[&formula] - consumed
+                        }
+                    };
+                (formula, remaining)
+            })
+            .collect())
     }
 
-    fn products_display_string(&self) -> String {
-        let balp: Vec<String> = self
+    /// Grams of `product` formed when the supplied reagent masses react to
+    /// completion.
+    pub fn theoretical_yield(
+        &self,
+        masses: &HashMap<String, f64>,
+        product: &str,
+    ) -> Result<f64, StoichError> {
+        let target = self
             .products
             .iter()
-            .map(|r| format!("{} {}", r.molar_coefficient, r.compound.formula))
-            .collect();
-        balp.join(" + ")
+            .find(|p| p.compound.formula == product)
+            .ok_or_else(|| {
+                StoichError::InvalidInput(format!(
+                    "{} is not a product in the reaction",
+                    product
+                ))
+            })?;
+        let (_, molrxn) = self.limiting_reagent(masses)?;
+        let exp_moles = molrxn * target.molar_coefficient as f64;
+        debug!("Theoretical moles of {}: {}", product, exp_moles);
+        let exp_grams = exp_moles * target.compound.molar_mass;
+        debug!("Theoretical yield of {} (g): {}", product, exp_grams);
+        Ok(exp_grams)
     }
 
-    pub fn display_string(&self) -> String {
-        format!(
-            "{} = {}",
-            self.reactants_display_string(),
-            self.products_display_string()
-        )
+    /// Percentage of the theoretical yield of `product` that `actual_mass`
+    /// grams represents.
+    pub fn percent_yield(
+        &self,
+        masses: &HashMap<String, f64>,
+        product: &str,
+        actual_mass: f64,
+    ) -> Result<f64, StoichError> {
+        let theoretical = self.theoretical_yield(masses, product)?;
+        Ok(actual_mass / theoretical * 100.0)
+    }
+}
+
+// mhchem reads `*` as a hydrate dot and wants isotope mass numbers braced,
+// as in `^{13}C`; charges and states can be passed through as written.
+fn mhchem_formula(formula: &str) -> String {
+    let mut latex = String::new();
+    let mut chars = formula.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '·' => latex.push('*'),
+            '^' if chars.peek().is_some_and(|d| d.is_ascii_digit()) => {
+                let mut digits = String::new();
+                while let Some(d) = chars.next_if(|d| d.is_ascii_digit()) {
+                    digits.push(d);
+                }
+                match chars.peek() {
+                    Some(next) if next.is_ascii_uppercase() => {
+                        latex.push_str(&format!("^{{{}}}", digits))
+                    }
+                    _ => latex.push_str(&format!("^{}", digits)),
+                }
+            }
+            c => latex.push(c),
+        }
+    }
+    latex
+}
+
+/// Formats as `2 Al + 3 Cl2 -> 2 AlCl3`, with `<=>` when reversible; the
+/// alternate form (`{:#}`) writes formulas with Unicode subscripts.
+impl fmt::Display for BalancedReaction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format_sides(self.arrow(), f.alternate(), false))
+    }
+}
+
+/// Assembles a `BalancedReaction` term by term. Errors are kept until
+/// `build`, which also checks that the reaction balances.
+#[derive(Debug, Clone, Default)]
+pub struct ReactionBuilder {
+    reactants: Vec<Reactant>,
+    products: Vec<Reactant>,
+    masses: HashMap<String, f64>,
+    error: Option<StoichError>,
+}
+
+impl ReactionBuilder {
+    pub fn new() -> Self {
+        ReactionBuilder::default()
+    }
+
+    fn reactant(&mut self, formula: &str, coeff: u32) -> Option<Reactant> {
+        if self.error.is_some() {
+            return None;
+        }
+        match Reactant::from_formula(formula, coeff) {
+            Ok(r) if coeff > 0 => Some(r),
+            Ok(_) => {
+                self.error = Some(StoichError::InvalidInput(format!(
+                    "Coefficient of {} must be positive",
+                    formula
+                )));
+                None
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+
+    pub fn add_reagent(mut self, formula: &str, coeff: u32) -> Self {
+        if let Some(r) = self.reactant(formula, coeff) {
+            self.reactants.push(r);
+        }
+        self
+    }
+
+    pub fn add_product(mut self, formula: &str, coeff: u32) -> Self {
+        if let Some(r) = self.reactant(formula, coeff) {
+            self.products.push(r);
+        }
+        self
+    }
+
+    /// Record the grams supplied of a reagent added with `add_reagent`,
+    /// for use with `build_with_masses`.
+    pub fn add_reagent_mass(mut self, formula: &str, grams: f64) -> Self {
+        if self.error.is_none() && (grams.is_nan() || grams <= 0.0) {
+            self.error = Some(StoichError::InvalidInput(format!(
+                "Mass of {} must be positive, got {}",
+                formula, grams
+            )));
+        }
+        self.masses.insert(formula.to_string(), grams);
+        self
+    }
+
+    pub fn build(self) -> Result<BalancedReaction, StoichError> {
+        self.build_with_masses().map(|(reaction, _)| reaction)
+    }
+
+    /// Like `build`, also returning the reagent masses keyed by formula, as
+    /// taken by `BalancedReaction::limiting_reagent`.
+    pub fn build_with_masses(
+        self,
+    ) -> Result<(BalancedReaction, HashMap<String, f64>), StoichError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if let Some(unknown) = self
+            .masses
+            .keys()
+            .find(|f| !self.reactants.iter().any(|r| &r.compound.formula == *f))
+        {
+            return Err(StoichError::InvalidInput(format!(
+                "{} is not a reagent in the reaction",
+                unknown
+            )));
+        }
+        let reaction = BalancedReaction::new(self.reactants, self.products);
+        if reaction.reactants.is_empty() || reaction.products.is_empty() {
+            return Err(StoichError::InvalidInput(
+                "Reaction needs at least one reagent and one product"
+                    .to_string(),
+            ));
+        }
+        if !reaction.is_balanced() {
+            return Err(StoichError::Unbalanceable(format!(
+                "Reaction {} is not balanced",
+                reaction
+            )));
+        }
+        Ok((reaction, self.masses))
     }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct YieldReaction {
     pub reagents: Vec<Substance>,
-    pub product: Substance,
+    pub products: Vec<Substance>,
 }
 
 impl YieldReaction {
-    pub fn new(reagents: Vec<Substance>, product: Substance) -> YieldReaction {
-        YieldReaction { reagents, product }
+    pub fn new(
+        reagents: Vec<Substance>,
+        products: Vec<Substance>,
+    ) -> YieldReaction {
+        YieldReaction { reagents, products }
+    }
+
+    pub fn one_to_one(reagent: Substance, product: Substance) -> YieldReaction {
+        YieldReaction::new(vec![reagent], vec![product])
     }
 
-    pub fn limiting_reagent(self: &Self) -> &Substance {
+    /// The coefficients of the reaction without the masses.
+    pub fn balanced(&self) -> BalancedReaction {
+        let reactants = |side: &[Substance]| -> Vec<Reactant> {
+            side.iter().map(|s| s.reactant.clone()).collect()
+        };
+        BalancedReaction::new(
+            reactants(&self.reagents),
+            reactants(&self.products),
+        )
+    }
+
+    pub fn limiting_reagent(&self) -> Result<&Substance, StoichError> {
         self.reagents
             .iter()
             .min_by(|l, r| {
@@ -197,26 +1087,652 @@ impl YieldReaction {
                     .partial_cmp(&r.molrxn())
                     .unwrap_or(Ordering::Equal)
             })
-            .map(|s| {
-                debug!("Limiting reagent is {}", s.reactant.compound.formula);
-                s
+            .inspect(|s| {
+                debug!("Limiting reagent is {}", s.reactant.compound.formula)
+            })
+            .ok_or_else(|| {
+                StoichError::InvalidInput(
+                    "Reaction has no reagents".to_string(),
+                )
             })
-            .unwrap()
     }
 
-    pub fn theoretical_yield(self: &Self) -> f32 {
-        let limiting = self.limiting_reagent();
+    fn first_product(&self) -> Result<&Substance, StoichError> {
+        self.products.first().ok_or_else(|| {
+            StoichError::InvalidInput("Reaction has no products".to_string())
+        })
+    }
+
+    fn product(&self, formula: &str) -> Result<&Substance, StoichError> {
+        self.products
+            .iter()
+            .find(|p| p.reactant.compound.formula == formula)
+            .ok_or_else(|| {
+                StoichError::InvalidInput(format!(
+                    "{} is not a product in the reaction",
+                    formula
+                ))
+            })
+    }
+
+    fn yield_of(&self, product: &Substance) -> Result<f64, StoichError> {
+        let limiting = self.limiting_reagent()?;
         trace!("{} moles of limiting reagent", limiting.moles());
         let exp_moles = limiting.moles()
-            * (self.product.reactant.molar_coefficient as f32
-                / limiting.reactant.molar_coefficient as f32);
+            * (product.reactant.molar_coefficient as f64
+                / limiting.reactant.molar_coefficient as f64);
         debug!("Theoretical moles of product: {}", exp_moles);
-        let exp_grams = exp_moles * self.product.reactant.compound.molar_mass;
+        let exp_grams = exp_moles * product.reactant.compound.molar_mass;
         debug!("Theoretical yield of product (g): {}", exp_grams);
-        exp_grams
+        Ok(exp_grams)
+    }
+
+    /// Theoretical yield in grams of the first product.
+    pub fn theoretical_yield(&self) -> Result<f64, StoichError> {
+        self.yield_of(self.first_product()?)
+    }
+
+    pub fn theoretical_yield_of(
+        &self,
+        formula: &str,
+    ) -> Result<f64, StoichError> {
+        self.yield_of(self.product(formula)?)
+    }
+
+    /// Percent yield of the first product.
+    pub fn percent_yield(&self) -> Result<f64, StoichError> {
+        let product = self.first_product()?;
+        Ok(product.mass / self.yield_of(product)? * 100.0)
+    }
+
+    pub fn percent_yield_of(&self, formula: &str) -> Result<f64, StoichError> {
+        let product = self.product(formula)?;
+        Ok(product.mass / self.yield_of(product)? * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    #[cfg(feature = "balance")]
+    use rug::Rational;
+
+    use crate::model::*;
+    #[cfg(feature = "balance")]
+    use crate::{parse::parse_equation, solve::balance};
+
+    fn _aluminium_chloride() -> BalancedReaction {
+        BalancedReaction::new(
+            vec![
+                Reactant::from_formula("Al", 2).unwrap(),
+                Reactant::from_formula("Cl2", 3).unwrap(),
+            ],
+            vec![Reactant::from_formula("AlCl3", 2).unwrap()],
+        )
+    }
+
+    fn _masses(masses: &[(&str, f64)]) -> HashMap<String, f64> {
+        masses.iter().map(|(f, m)| (f.to_string(), *m)).collect()
+    }
+
+    fn _percent(composition: &HashMap<Element, f64>, symbol: &str) -> f64 {
+        composition[&Element::from_symbol(symbol).unwrap()]
+    }
+
+    #[test]
+    fn percent_composition_water() {
+        let water = Compound::from_formula("H2O").unwrap();
+        let composition = water.percent_composition();
+        assert!((_percent(&composition, "H") - 11.19).abs() < 0.01);
+        assert!((_percent(&composition, "O") - 88.81).abs() < 0.01);
+    }
+
+    #[test]
+    fn percent_composition_groups() {
+        let hydroxide = Compound::from_formula("Ca(OH)2").unwrap();
+        let composition = hydroxide.percent_composition();
+        assert_eq!(composition.len(), 3);
+        assert!((_percent(&composition, "Ca") - 54.09).abs() < 0.01);
+        let total: f64 = composition.values().sum();
+        assert!((total - 100.0).abs() < 1e-3, "{}", total);
+    }
+
+    #[test]
+    fn percent_composition_ion() {
+        let sulfate = Compound::from_formula("SO4^2-").unwrap();
+        let composition = sulfate.percent_composition();
+        let total: f64 = composition.values().sum();
+        assert!((total - 100.0).abs() < 1e-9, "{}", total);
+        assert!((_percent(&composition, "S") - 33.38).abs() < 0.01);
+    }
+
+    #[test]
+    fn display_reaction() {
+        let reaction = _aluminium_chloride();
+        assert_eq!(reaction.to_string(), "2 Al + 3 Cl2 -> 2 AlCl3");
+        let water = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("H2", 2).unwrap(),
+                Reactant::from_formula("O2", 1).unwrap(),
+            ],
+            vec![Reactant::from_formula("H2O", 2).unwrap()],
+        );
+        assert_eq!(water.to_string(), "2 H2 + O2 -> 2 H2O");
+        assert_eq!(water.display_string(), "2 H2 + 1 O2 = 2 H2O");
+        assert_eq!(format!("{:#}", water), "2 H₂ + O₂ -> 2 H₂O");
+    }
+
+    #[test]
+    fn mass_conservation() {
+        let reaction = _aluminium_chloride();
+        let aluminium = reaction.reactants[0].mass_contribution().unwrap();
+        assert!((aluminium - 53.963).abs() < 1e-3, "{}", aluminium);
+        reaction.verify_mass_conservation().unwrap();
+        let unbalanced = BalancedReaction::new(
+            vec![Reactant::from_formula("H2", 1).unwrap()],
+            vec![Reactant::from_formula("H2O", 1).unwrap()],
+        );
+        assert!(unbalanced.verify_mass_conservation().is_err());
+        let reduction = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("Fe3+", 1).unwrap(),
+                Reactant::of_compound(Compound::electron(), 1),
+            ],
+            vec![Reactant::from_formula("Fe2+", 1).unwrap()],
+        );
+        reduction.verify_mass_conservation().unwrap();
+    }
+
+    #[test]
+    fn empirical_form() {
+        let glucose = Compound::from_formula("C6H12O6").unwrap();
+        assert!(!glucose.is_empirical());
+        let empirical = glucose.empirical_form();
+        assert_eq!(empirical.formula, "CH2O");
+        assert!(empirical.is_empirical());
+        assert!(
+            (empirical.molar_mass() * 6.0 - glucose.molar_mass()).abs() < 1e-9
+        );
+        let water = Compound::from_formula("H2O").unwrap();
+        assert!(water.is_empirical());
+        assert_eq!(water.empirical_form(), water);
+        let peroxide = Compound::from_formula("H2O2").unwrap();
+        assert_eq!(peroxide.empirical_form().formula, "HO");
+        let benzene = Compound::from_formula("C6H6").unwrap();
+        assert_eq!(benzene.empirical_form().formula, "CH");
+        assert_eq!(
+            Compound::from_formula("Hg2^2+")
+                .unwrap()
+                .empirical_form()
+                .formula,
+            "Hg"
+        );
+    }
+
+    #[test]
+    fn unicode_formulas() {
+        let formula = |f: &str| Compound::from_formula(f).unwrap();
+        assert_eq!(formula("Al2O3").to_unicode_string(), "Al₂O₃");
+        assert_eq!(formula("Ca(OH)2").to_unicode_string(), "Ca(OH)₂");
+        assert_eq!(formula("C12H22O11").to_unicode_string(), "C₁₂H₂₂O₁₁");
+        assert_eq!(formula("CuSO4·5H2O").to_unicode_string(), "CuSO₄·5H₂O");
+        assert_eq!(formula("SO4^2-").to_unicode_string(), "SO₄^2-");
+        assert_eq!(formula("Fe3+").to_unicode_string(), "Fe3+");
+        assert_eq!(
+            format!("{:#}", _aluminium_chloride()),
+            "2 Al + 3 Cl₂ -> 2 AlCl₃"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn state_symbols() {
+        let salt = Compound::from_formula("NaCl(aq)").unwrap();
+        assert_eq!(salt.state, Some(State::Aqueous));
+        assert_eq!(salt.atoms, Compound::from_formula("NaCl").unwrap().atoms);
+        let sulfate = Compound::from_formula("SO4^2-(aq)").unwrap();
+        assert_eq!((sulfate.charge, sulfate.state), (-2, Some(State::Aqueous)));
+        assert_eq!(sulfate.to_unicode_string(), "SO₄^2-(aq)");
+        let water = Compound::from_formula("H2O(l)").unwrap();
+        assert_eq!(water.state, Some(State::Liquid));
+        assert_eq!(Compound::from_formula("H2O").unwrap().state, None);
+        let (reagents, products) =
+            parse_equation("Na+(aq) + Cl-(aq) -> NaCl(s)").unwrap();
+        let reaction = balance(reagents, products).unwrap();
+        assert_eq!(reaction.to_string(), "Na+(aq) + Cl-(aq) -> NaCl(s)");
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn display_round_trip() {
+        let reaction = _aluminium_chloride();
+        let (reagents, products) =
+            parse_equation(&reaction.to_string()).unwrap();
+        assert_eq!(balance(reagents, products).unwrap(), reaction);
+    }
+
+    #[test]
+    fn substance_from_moles() {
+        let water = Substance::from_moles("H2O", 2.0).unwrap();
+        assert!((water.mass() - 36.03).abs() < 0.01, "{}", water.mass());
+        assert!((water.moles() - 2.0).abs() < 1e-6);
+        assert!(Substance::from_moles("", 1.0).is_err());
+    }
+
+    #[test]
+    fn substance_particles() {
+        let water = Substance::from_formula("H2O", 18.015, 1).unwrap();
+        let particles = water.particles();
+        assert!((particles / AVOGADRO - 1.0).abs() < 1e-4, "{}", particles);
+        let back = Substance::from_particles("H2O", particles).unwrap();
+        assert!((back.moles() - water.moles()).abs() < 1e-6);
+        assert!((back.mass() - 18.015).abs() < 1e-3);
+    }
+
+    #[test]
+    fn limiting_reagent() {
+        let reaction = _aluminium_chloride();
+        let masses = _masses(&[("Al", 2.8), ("Cl2", 4.25)]);
+        let (limiting, molrxn) = reaction.limiting_reagent(&masses).unwrap();
+        assert_eq!(limiting.compound.formula, "Cl2");
+        assert!((molrxn - 4.25 / 70.9 / 3.0).abs() < 1e-5, "{}", molrxn);
+        let masses = _masses(&[("Al", 0.5), ("Cl2", 4.25)]);
+        let (limiting, _) = reaction.limiting_reagent(&masses).unwrap();
+        assert_eq!(limiting.compound.formula, "Al");
+    }
+
+    #[test]
+    fn theoretical_yield() {
+        let reaction = _aluminium_chloride();
+        let masses = _masses(&[("Al", 2.8), ("Cl2", 4.25)]);
+        let grams = reaction.theoretical_yield(&masses, "AlCl3").unwrap();
+        assert!((grams - 5.328).abs() < 1e-3, "{}", grams);
+    }
+
+    #[test]
+    fn theoretical_yield_unknown_product() {
+        let reaction = _aluminium_chloride();
+        let masses = _masses(&[("Al", 2.8), ("Cl2", 4.25)]);
+        let result = reaction.theoretical_yield(&masses, "Al2O3");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Al2O3 is not a product"));
+    }
+
+    #[test]
+    fn percent_yield() {
+        let reaction = _aluminium_chloride();
+        let masses = _masses(&[("Al", 2.8), ("Cl2", 4.25)]);
+        let pct = reaction.percent_yield(&masses, "AlCl3", 4.889).unwrap();
+        assert!((pct - 91.76).abs() < 0.01, "{}", pct);
+    }
+
+    #[test]
+    fn yield_reaction_percent_yield() {
+        let reaction = YieldReaction::new(
+            vec![
+                Substance::from_formula("Al", 2.8, 2).unwrap(),
+                Substance::from_formula("Cl2", 4.25, 3).unwrap(),
+            ],
+            vec![Substance::from_formula("AlCl3", 4.889, 2).unwrap()],
+        );
+        let pct = reaction.percent_yield().unwrap();
+        assert!((pct - 91.76).abs() < 0.01, "{}", pct);
+        assert_eq!(reaction.balanced(), _aluminium_chloride());
+    }
+
+    #[test]
+    fn yield_reaction_empty_side() {
+        let aluminium = Substance::from_formula("Al", 2.8, 2).unwrap();
+        let no_products = YieldReaction::new(vec![aluminium.clone()], vec![]);
+        assert!(no_products.theoretical_yield().is_err());
+        assert!(no_products.percent_yield().is_err());
+        let no_reagents = YieldReaction::new(vec![], vec![aluminium]);
+        assert!(no_reagents.limiting_reagent().is_err());
+        assert!(no_reagents.theoretical_yield().is_err());
+        assert!(no_reagents.percent_yield_of("Al").is_err());
+    }
+
+    #[test]
+    fn limiting_reagent_missing_mass() {
+        let reaction = _aluminium_chloride();
+        let result = reaction.limiting_reagent(&_masses(&[("Al", 2.8)]));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No mass supplied for Cl2"));
+        let masses = _masses(&[("Al", 2.8), ("Cl2", 4.25), ("Fe", 1.0)]);
+        let result = reaction.limiting_reagent(&masses);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Fe is not a reagent"));
+    }
+
+    #[cfg(all(feature = "serde", feature = "balance"))]
+    #[test]
+    fn serde_round_trip() {
+        let (reagents, products) = parse_equation("Al + Cl2 = AlCl3").unwrap();
+        let reaction = balance(reagents, products).unwrap();
+        let json = serde_json::to_value(&reaction).unwrap();
+        assert_eq!(json["reagents"][1]["molar_coefficient"], 3);
+        assert_eq!(json["products"][0]["compound"]["atoms"]["Cl"], 3);
+        let back: BalancedReaction = serde_json::from_value(json).unwrap();
+        assert_eq!(back, reaction);
+        assert_eq!(
+            back.products[0].compound.atoms,
+            reaction.products[0].compound.atoms
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_recomputes_molar_mass() {
+        let sulfate = Compound::from_formula("SO4^2-").unwrap();
+        let json = serde_json::to_value(&sulfate).unwrap();
+        assert!(json.get("molar_mass").is_none());
+        let back: Compound = serde_json::from_value(json).unwrap();
+        assert_eq!(back.molar_mass(), sulfate.molar_mass());
+        let tampered = serde_json::json!({
+            "formula": "H2O",
+            "atoms": { "C": 1 },
+            "charge": 0,
+            "state": null,
+            "molar_mass": 1000.0,
+        });
+        let carbon: Compound = serde_json::from_value(tampered).unwrap();
+        let expected = Compound::from_formula("C").unwrap().molar_mass();
+        assert_eq!(carbon.molar_mass(), expected);
+        let electron: Compound = serde_json::from_value(
+            serde_json::to_value(Compound::electron()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(electron.molar_mass(), ELECTRON_MOLAR_MASS);
+    }
+
+    #[test]
+    fn yield_reaction_several_products() {
+        let reaction = YieldReaction::new(
+            vec![Substance::from_formula("CaCO3", 10.0, 1).unwrap()],
+            vec![
+                Substance::from_formula("CaO", 5.0, 1).unwrap(),
+                Substance::from_formula("CO2", 4.0, 1).unwrap(),
+            ],
+        );
+        let cao = reaction.theoretical_yield_of("CaO").unwrap();
+        assert!((cao - 5.603).abs() < 0.01, "{}", cao);
+        let co2 = reaction.percent_yield_of("CO2").unwrap();
+        assert!((co2 - 90.89).abs() < 0.1, "{}", co2);
+        assert!(reaction.percent_yield_of("H2O").is_err());
+        let single = YieldReaction::one_to_one(
+            Substance::from_formula("CaCO3", 10.0, 1).unwrap(),
+            Substance::from_formula("CaO", 5.0, 1).unwrap(),
+        );
+        assert_eq!(single.theoretical_yield().unwrap(), cao);
+    }
+
+    #[test]
+    fn ion_electron_correction() {
+        let mut sulfate = Compound::from_formula("SO4^2-").unwrap();
+        let neutral = Compound::from_formula("SO4").unwrap();
+        let shift = sulfate.molar_mass() - neutral.molar_mass();
+        assert!(
+            (shift - 2.0 * ELECTRON_MOLAR_MASS).abs() < 1e-5,
+            "{}",
+            shift
+        );
+        let mono = sulfate.monoisotopic_mass().unwrap();
+        assert!((mono - 95.952_81).abs() < 1e-4, "{}", mono);
+        assert_eq!(
+            sulfate.molecular_weight().unwrap(),
+            neutral.molecular_weight().unwrap()
+        );
+        sulfate.set_electron_correction(false).unwrap();
+        assert_eq!(sulfate.molar_mass(), neutral.molar_mass());
+        assert_eq!(
+            sulfate.monoisotopic_mass().unwrap(),
+            neutral.monoisotopic_mass().unwrap()
+        );
+    }
+
+    #[test]
+    fn from_str() {
+        let water: Compound = "H2O".parse().unwrap();
+        assert_eq!(water.formula, "H2O");
+        assert!("H2Xx".parse::<Compound>().is_err());
+        let reactant: Reactant = "2 H2O".parse().unwrap();
+        assert_eq!(reactant.molar_coefficient, 2);
+        assert_eq!(reactant.compound.atoms, water.atoms);
+        let reactant: Reactant = " NaCl(aq) ".parse().unwrap();
+        assert_eq!(reactant.molar_coefficient, 1);
+        assert_eq!(reactant.compound.state, Some(State::Aqueous));
+        assert!("0 H2O".parse::<Reactant>().is_err());
+        assert!("99999999999 H2O".parse::<Reactant>().is_err());
+        let err = "3 H2(O".parse::<Reactant>().unwrap_err();
+        assert_eq!(err.position(), Some(4));
+    }
+
+    #[test]
+    fn sorted_elements() {
+        let compound = Compound::from_formula("NaHCO3").unwrap();
+        let symbols: Vec<(&str, u32)> = compound
+            .elements()
+            .map(|(e, count)| (e.get_symbol(), count))
+            .collect();
+        assert_eq!(symbols, vec![("C", 1), ("H", 1), ("Na", 1), ("O", 3)]);
+        let labelled = Compound::from_formula("^13CCH3D").unwrap();
+        let notation: Vec<String> =
+            labelled.elements().map(|(e, _)| e.notation()).collect();
+        assert_eq!(notation, vec!["C", "^13C", "H", "^2H"]);
+    }
+
+    #[test]
+    fn hill_formula() {
+        let hill = |f: &str| Compound::from_formula(f).unwrap().hill_formula();
+        assert_eq!(hill("C6H12O6"), "C6H12O6");
+        assert_eq!(hill("H2SO4"), "H2O4S");
+        assert_eq!(hill("CH3CH2OH"), "C2H6O");
+        assert_eq!(hill("NaHCO3"), "CHNaO3");
+        assert_eq!(hill("BH3"), "BH3");
+        assert_eq!(hill("Cu(NH3)4^2+"), "CuH12N4");
+    }
+
+    #[test]
+    fn canonical_key() {
+        let key = |f: &str| Compound::from_formula(f).unwrap().canonical_key();
+        assert_eq!(key("NaOH"), "HNaO");
+        assert_eq!(key("SO4^2-(aq)"), "O4S^2-(aq)");
+        assert_eq!(key("NH4+"), "H4N+");
+        let compounds: HashSet<Compound> = ["NaOH", "HONa", "OHNa", "Na+"]
+            .iter()
+            .map(|f| Compound::from_formula(f).unwrap())
+            .collect();
+        assert_eq!(compounds.len(), 2);
+        assert_ne!(
+            Compound::from_formula("H2O(l)").unwrap(),
+            Compound::from_formula("H2O(g)").unwrap()
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn mole_ratio() {
+        let (rg, pd) = parse_equation("H2 + O2 = H2O").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        assert_eq!(
+            reaction.mole_ratio("O2", "H2O").unwrap(),
+            Rational::from((1, 2))
+        );
+        assert_eq!(reaction.mole_ratio("H2", "H2O").unwrap(), 1);
+        assert_eq!(reaction.mole_ratio("H2", "O2").unwrap(), 2);
+        assert!(reaction.mole_ratio("N2", "H2O").is_err());
+    }
+
+    #[test]
+    fn mass_to_mass() {
+        let reaction = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("H2", 2).unwrap(),
+                Reactant::from_formula("O2", 1).unwrap(),
+            ],
+            vec![Reactant::from_formula("H2O", 2).unwrap()],
+        );
+        let water = reaction.mass_to_mass("H2", 4.032, "H2O").unwrap();
+        assert!((water - 36.03).abs() < 0.01, "{}", water);
+        let oxygen = reaction.mass_to_mass("H2O", 36.03, "O2").unwrap();
+        assert!((oxygen - 32.0).abs() < 0.01, "{}", oxygen);
+        assert!(reaction.mass_to_mass("H2", 1.0, "CO2").is_err());
+        assert!(reaction.mass_to_mass("CO2", 1.0, "H2").is_err());
+    }
+
+    #[test]
+    fn excess_reagents() {
+        let reaction = _aluminium_chloride();
+        let left = reaction
+            .excess_reagents(&_masses(&[("Al", 10.0), ("Cl2", 20.0)]))
+            .unwrap();
+        assert_eq!(left["Cl2"], 0.0);
+        // 20 g Cl2 consumes 2/3 * 20 / 70.9 mol Al
+        assert!((left["Al"] - 4.926).abs() < 0.01, "{}", left["Al"]);
+        assert!(reaction.excess_reagents(&HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn equality_ignores_order() {
+        let reaction = _aluminium_chloride();
+        let swapped = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("Cl2", 3).unwrap(),
+                Reactant::from_formula("Al", 2).unwrap(),
+            ],
+            reaction.products.clone(),
+        );
+        assert_eq!(reaction, swapped);
+        let doubled = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("Al", 2).unwrap(),
+                Reactant::from_formula("Cl2", 3).unwrap(),
+                Reactant::from_formula("Al", 5).unwrap(),
+            ],
+            reaction.products.clone(),
+        );
+        assert_ne!(reaction, doubled);
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn latex() {
+        assert_eq!(
+            _aluminium_chloride().to_latex(),
+            r"\ce{2 Al + 3 Cl2 -> 2 AlCl3}"
+        );
+        let (rg, pd) =
+            parse_equation("Ba^2+(aq) + SO4^2-(aq) = BaSO4(s)").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        assert_eq!(
+            reaction.to_latex(),
+            r"\ce{Ba^2+(aq) + SO4^2-(aq) -> BaSO4(s)}"
+        );
+        let hydrate = BalancedReaction::new(
+            vec![Reactant::from_formula("CuSO4·5H2O", 1).unwrap()],
+            vec![Reactant::from_formula("^13CO2", 2).unwrap()],
+        );
+        assert_eq!(hydrate.to_latex(), r"\ce{CuSO4*5H2O -> 2 ^{13}CO2}");
+    }
+
+    #[test]
+    fn reversible_reaction() {
+        let mut reaction = _aluminium_chloride();
+        reaction.reversible = true;
+        assert_eq!(reaction.to_string(), "2 Al + 3 Cl2 <=> 2 AlCl3");
+        assert_eq!(reaction.display_string(), "2 Al + 3 Cl2 <=> 2 AlCl3");
+        assert_eq!(reaction.to_latex(), r"\ce{2 Al + 3 Cl2 <=> 2 AlCl3}");
+        assert_ne!(reaction, _aluminium_chloride());
+    }
+
+    #[test]
+    fn abbreviated_formula() {
+        let ethyl_acetate = Compound::from_formula_with("EtOAc", true).unwrap();
+        assert_eq!(ethyl_acetate.formula, "EtOAc");
+        assert_eq!(ethyl_acetate.hill_formula(), "C4H8O2");
+        let actinium = Compound::from_formula_with("AcCl3", false).unwrap();
+        assert_eq!(actinium.hill_formula(), "AcCl3");
+        assert!(Compound::from_formula("MeOH").is_err());
+    }
+
+    #[test]
+    fn reaction_builder() {
+        let reaction = ReactionBuilder::new()
+            .add_reagent("Al", 2)
+            .add_reagent("Cl2", 3)
+            .add_product("AlCl3", 2)
+            .build()
+            .unwrap();
+        assert_eq!(reaction, _aluminium_chloride());
+        let (reaction, masses) = ReactionBuilder::new()
+            .add_reagent("Al", 2)
+            .add_reagent("Cl2", 3)
+            .add_product("AlCl3", 2)
+            .add_reagent_mass("Al", 10.0)
+            .add_reagent_mass("Cl2", 20.0)
+            .build_with_masses()
+            .unwrap();
+        let (limiting, _) = reaction.limiting_reagent(&masses).unwrap();
+        assert_eq!(limiting.compound.formula, "Cl2");
+    }
+
+    #[test]
+    fn reaction_builder_errors() {
+        let unbalanced = ReactionBuilder::new()
+            .add_reagent("Al", 1)
+            .add_reagent("Cl2", 3)
+            .add_product("AlCl3", 2)
+            .build();
+        assert!(unbalanced.unwrap_err().to_string().contains("not balanced"));
+        let unknown = ReactionBuilder::new()
+            .add_reagent("Xx", 1)
+            .add_product("H2O", 1)
+            .build();
+        assert!(unknown.is_err());
+        let stray_mass = ReactionBuilder::new()
+            .add_reagent("H2O", 1)
+            .add_product("H2O", 1)
+            .add_reagent_mass("NaCl", 1.0)
+            .build();
+        assert!(stray_mass
+            .unwrap_err()
+            .to_string()
+            .contains("not a reagent"));
+        assert!(ReactionBuilder::new().build().is_err());
     }
 
-    pub fn percent_yield(self: &Self) -> f32 {
-        self.product.mass / self.theoretical_yield()
+    #[test]
+    fn equilibrium_expression() {
+        let ammonia = ReactionBuilder::new()
+            .add_reagent("N2(g)", 1)
+            .add_reagent("H2(g)", 3)
+            .add_product("NH3(g)", 2)
+            .build()
+            .unwrap();
+        assert_eq!(
+            ammonia.equilibrium_expression(),
+            "Kc = [NH3]^2 / [N2][H2]^3"
+        );
+        let limestone = ReactionBuilder::new()
+            .add_reagent("CaCO3(s)", 1)
+            .add_product("CaO(s)", 1)
+            .add_product("CO2(g)", 1)
+            .build()
+            .unwrap();
+        assert_eq!(limestone.equilibrium_expression(), "Kc = [CO2]");
+        let water = ReactionBuilder::new()
+            .add_reagent("H2(g)", 2)
+            .add_reagent("O2(g)", 1)
+            .add_product("H2O(l)", 2)
+            .build()
+            .unwrap();
+        assert_eq!(water.equilibrium_expression(), "Kc = 1 / [H2]^2[O2]");
     }
 }
@@ -1,28 +1,566 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::panic;
 
-use crate::model::Element;
+use crate::error::StoichError;
+use crate::isotope::{isotope, isotopes, principal_isotope};
+use crate::model::{Compound, Element, ELECTRON_MOLAR_MASS};
+use crate::parse::{get_element, get_element_by_id};
 
-pub fn molecular_weight(atoms: HashMap<Element, u32>) -> Result<f32, String> {
-    let mut weight: f32 = 0 as f32;
+const MAX_EMPIRICAL_MULTIPLIER: u32 = 8;
+const ALKALI_METALS: [&str; 6] = ["Li", "Na", "K", "Rb", "Cs", "Fr"];
+const ALKALINE_EARTH_METALS: [&str; 6] = ["Be", "Mg", "Ca", "Sr", "Ba", "Ra"];
+const EMPIRICAL_TOLERANCE: f64 = 0.1;
+// Combinations rarer than this are dropped while convolving.
+const PATTERN_PRUNE: f64 = 1e-12;
+// How far an element's tabulated abundances may sum from 1.
+const ABUNDANCE_TOLERANCE: f64 = 1e-3;
+
+// Uncertainties of the IUPAC abridged standard atomic weights.
+const WEIGHT_UNCERTAINTIES: [(&str, f64); 47] = [
+    ("H", 0.0002),
+    ("He", 0.0001),
+    ("Li", 0.06),
+    ("Be", 0.0001),
+    ("B", 0.02),
+    ("C", 0.002),
+    ("N", 0.001),
+    ("O", 0.001),
+    ("F", 0.001),
+    ("Ne", 0.001),
+    ("Na", 0.001),
+    ("Mg", 0.002),
+    ("Al", 0.001),
+    ("Si", 0.001),
+    ("P", 0.001),
+    ("S", 0.02),
+    ("Cl", 0.01),
+    ("Ar", 0.16),
+    ("K", 0.001),
+    ("Ca", 0.004),
+    ("Sc", 0.001),
+    ("Ti", 0.001),
+    ("V", 0.001),
+    ("Cr", 0.001),
+    ("Mn", 0.001),
+    ("Fe", 0.002),
+    ("Co", 0.001),
+    ("Ni", 0.001),
+    ("Cu", 0.003),
+    ("Zn", 0.02),
+    ("Ga", 0.001),
+    ("Ge", 0.008),
+    ("As", 0.001),
+    ("Se", 0.008),
+    ("Br", 0.003),
+    ("Kr", 0.002),
+    ("Rb", 0.001),
+    ("Sr", 0.01),
+    ("Ag", 0.01),
+    ("Sn", 0.01),
+    ("I", 0.01),
+    ("Ba", 0.01),
+    ("Pt", 0.02),
+    ("Au", 0.01),
+    ("Hg", 0.01),
+    ("Pb", 1.1),
+    ("U", 0.01),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ElementInfo {
+    pub symbol: &'static str,
+    pub name: &'static str,
+    pub atomic_number: u8,
+    pub atomic_weight: f64,
+}
+
+impl From<Element> for ElementInfo {
+    fn from(element: Element) -> Self {
+        ElementInfo {
+            symbol: element.get_symbol(),
+            name: element.get_name(),
+            atomic_number: element.get_atomic_number(),
+            atomic_weight: element.get_atomic_mass(),
+        }
+    }
+}
+
+/// Atomic weights by element symbol, for materials whose isotopic
+/// composition differs from the standard one. The default overrides
+/// nothing, so every element has its standard weight.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AtomicWeights {
+    weights: HashMap<&'static str, f64>,
+}
+
+impl AtomicWeights {
+    /// Override the weight of the element with `symbol`.
+    pub fn set(
+        &mut self,
+        symbol: &str,
+        weight: f64,
+    ) -> Result<(), StoichError> {
+        if weight.is_nan() || weight <= 0.0 {
+            return Err(StoichError::InvalidInput(format!(
+                "Invalid atomic weight {} for {}",
+                weight, symbol
+            )));
+        }
+        let element = get_element(symbol)?;
+        self.weights.insert(element.get_symbol(), weight);
+        Ok(())
+    }
+
+    /// The weight used for `element`; isotope-labelled atoms always weigh
+    /// their isotope's mass.
+    pub fn get(&self, element: &Element) -> f64 {
+        match element.get_mass_number() {
+            Some(_) => element.get_atomic_mass(),
+            None => self
+                .weights
+                .get(element.get_symbol())
+                .copied()
+                .unwrap_or_else(|| element.get_atomic_mass()),
+        }
+    }
+}
+
+/// Molar masses memoized by formula, for code that looks up the same
+/// formulas many times, such as yield tables. Formulas are matched as
+/// written, because putting them in canonical form would mean parsing
+/// them, and a hit skips parsing altogether: `H2O` and `HOH` are separate
+/// entries. The balancing batch functions take no cache, since they need
+/// parsed compounds rather than weights.
+#[derive(Debug, Clone, Default)]
+pub struct MolecularWeightCache {
+    weights: HashMap<String, f64>,
+}
+
+impl MolecularWeightCache {
+    pub fn new() -> Self {
+        MolecularWeightCache::default()
+    }
+
+    /// The molar mass of `formula`, parsed only the first time it is seen.
+    pub fn get(&mut self, formula: &str) -> Result<f64, StoichError> {
+        if let Some(weight) = self.weights.get(formula) {
+            return Ok(*weight);
+        }
+        let weight = Compound::from_formula(formula)?.molar_mass();
+        self.weights.insert(formula.to_string(), weight);
+        Ok(weight)
+    }
+
+    pub fn len(&self) -> usize {
+        self.weights.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+}
+
+/// Look up an element by its exact, case-sensitive symbol.
+pub fn element_by_symbol(symbol: &str) -> Result<ElementInfo, StoichError> {
+    get_element(symbol).map(ElementInfo::from)
+}
+
+/// Look up an element by its English name, ignoring case.
+pub fn element_by_name(name: &str) -> Result<ElementInfo, StoichError> {
+    let e: Result<Option<Element>, _> =
+        panic::catch_unwind(|| Element::from_name(name));
+    e.unwrap_or(None)
+        .map(ElementInfo::from)
+        .ok_or_else(|| StoichError::UnknownElement(name.to_string()))
+}
+
+pub fn element_by_number(
+    atomic_number: u8,
+) -> Result<ElementInfo, StoichError> {
+    get_element_by_id(atomic_number).map(ElementInfo::from)
+}
+
+pub fn molecular_weight(
+    atoms: BTreeMap<Element, u32>,
+) -> Result<f64, StoichError> {
+    Ok(atoms
+        .iter()
+        .map(|(element, count)| element.get_atomic_mass() * *count as f64)
+        .sum())
+}
+
+/// Molecular weight using `weights` in place of the standard atomic weights.
+pub fn molecular_weight_with(
+    atoms: BTreeMap<Element, u32>,
+    weights: &AtomicWeights,
+) -> Result<f64, StoichError> {
+    let mut weight: f64 = 0.0;
     for (element, count) in atoms {
-        let mass = element.get_atomic_mass();
+        let mass = weights.get(&element);
         trace!("Adding {:?} x {:?} for element {:?}", count, mass, element);
-        weight += mass * count as f32
+        weight += mass * count as f64
     }
     Ok(weight)
 }
 
+/// Molecular weight and its standard uncertainty, propagated in quadrature
+/// from the atomic weight uncertainties. Isotope-labelled atoms are taken
+/// as exact.
+pub fn molecular_weight_with_uncertainty(
+    atoms: &BTreeMap<Element, u32>,
+) -> Result<(f64, f64), StoichError> {
+    let mut weight = 0.0;
+    let mut variance = 0.0;
+    for (element, count) in atoms {
+        let uncertainty = match element.get_mass_number() {
+            Some(_) => 0.0,
+            None => WEIGHT_UNCERTAINTIES
+                .iter()
+                .find(|(symbol, _)| *symbol == element.get_symbol())
+                .map(|(_, u)| *u)
+                .ok_or_else(|| {
+                    StoichError::InvalidInput(format!(
+                        "No atomic weight uncertainty for {}",
+                        element.get_symbol()
+                    ))
+                })?,
+        };
+        weight += element.get_atomic_mass() * *count as f64;
+        variance += (uncertainty * *count as f64).powi(2);
+    }
+    Ok((weight, variance.sqrt()))
+}
+
+/// Mass of the molecule made only of each element's most abundant isotope,
+/// as seen in mass spectrometry.
+pub fn monoisotopic_mass(
+    atoms: &BTreeMap<Element, u32>,
+) -> Result<f64, StoichError> {
+    atoms
+        .iter()
+        .map(|(element, count)| {
+            let isotope = match element.get_mass_number() {
+                Some(n) => isotope(element, n),
+                None => principal_isotope(element),
+            };
+            isotope
+                .map(|isotope| isotope.mass * *count as f64)
+                .ok_or_else(|| {
+                    StoichError::InvalidInput(format!(
+                        "No isotope data for {}",
+                        element.get_symbol()
+                    ))
+                })
+        })
+        .sum()
+}
+
+/// Correct the mass of a neutral molecule for the electrons lost (positive
+/// `charge`) or gained (negative `charge`) by its ion.
+pub fn ion_mass(neutral_mass: f64, charge: i32) -> f64 {
+    neutral_mass - charge as f64 * ELECTRON_MOLAR_MASS
+}
+
+/// The theoretical isotope distribution of a molecule as `(m/z, intensity)`
+/// peaks, one per nominal mass, with the base peak scaled to 100 and peaks
+/// below `threshold` left out.
+pub fn isotope_pattern(
+    atoms: &BTreeMap<Element, u32>,
+    threshold: f64,
+) -> Result<Vec<(f64, f64)>, StoichError> {
+    // nominal mass -> (abundance-weighted mass, abundance)
+    let mut pattern: BTreeMap<u32, (f64, f64)> = BTreeMap::new();
+    pattern.insert(0, (0.0, 1.0));
+    for (element, count) in atoms {
+        let available = match element.get_mass_number() {
+            Some(n) => isotope(element, n)
+                .map(|i| vec![(i.mass_number as u32, i.mass, 1.0)])
+                .unwrap_or_default(),
+            None => isotopes(element)
+                .iter()
+                .filter(|i| i.abundance > 0.0)
+                .map(|i| (i.mass_number as u32, i.mass, i.abundance))
+                .collect(),
+        };
+        if available.is_empty() {
+            return Err(StoichError::InvalidInput(format!(
+                "No isotope data for {}",
+                element.get_symbol()
+            )));
+        }
+        let total: f64 =
+            available.iter().map(|(_, _, abundance)| abundance).sum();
+        if (total - 1.0).abs() > ABUNDANCE_TOLERANCE {
+            return Err(StoichError::InvalidInput(format!(
+                "Isotope abundances of {} sum to {}, not 1",
+                element.get_symbol(),
+                total
+            )));
+        }
+        for _ in 0..*count {
+            let mut next: BTreeMap<u32, (f64, f64)> = BTreeMap::new();
+            for (nominal, (mass, abundance)) in &pattern {
+                for (number, isotope_mass, isotope_abundance) in &available {
+                    let p = abundance * isotope_abundance;
+                    if p < PATTERN_PRUNE {
+                        continue;
+                    }
+                    let peak =
+                        next.entry(nominal + number).or_insert((0.0, 0.0));
+                    peak.0 += (mass + isotope_mass) * p;
+                    peak.1 += p;
+                }
+            }
+            pattern = next
+                .into_iter()
+                .map(|(nominal, (weighted, p))| (nominal, (weighted / p, p)))
+                .collect();
+        }
+    }
+    let base = pattern.values().map(|(_, p)| *p).fold(0.0, f64::max);
+    trace!("Isotope pattern {:?}, base abundance {}", pattern, base);
+    Ok(pattern
+        .values()
+        .map(|(mass, p)| (*mass, p / base * 100.0))
+        .filter(|(_, intensity)| *intensity >= threshold)
+        .collect())
+}
+
+/// Assign an oxidation number to every atom of `compound`, keyed by element
+/// and the atom's index among that element's atoms. Fluorine, group 1 and 2
+/// metals and hydrogen (-1 in metal hydrides) are fixed first; oxygen is
+/// then -2 and chlorine, bromine and iodine -1 where another element is
+/// still unknown, and the last unknown element takes up the net charge.
+/// Where that charge does not divide evenly, as in `Fe3O4`, its atoms
+/// differ by one.
+pub fn oxidation_states(
+    compound: &Compound,
+) -> Result<HashMap<(Element, usize), i32>, StoichError> {
+    let mut totals: HashMap<Element, i32> = HashMap::new();
+    let mut unknown: Vec<(Element, u32)> =
+        compound.elements().map(|(e, count)| (*e, count)).collect();
+    let only_metals_besides_h = unknown
+        .iter()
+        .all(|(e, _)| e.get_symbol() == "H" || e.is_metal());
+    let mut fix = |unknown: &mut Vec<(Element, u32)>,
+                   rule: &dyn Fn(&Element) -> Option<i32>| {
+        unknown.retain(|(e, count)| match rule(e) {
+            Some(state) => {
+                totals.insert(*e, state * *count as i32);
+                false
+            }
+            None => true,
+        });
+    };
+    if unknown.len() > 1 {
+        fix(&mut unknown, &|e| match e.get_symbol() {
+            "F" => Some(-1),
+            "H" if only_metals_besides_h => Some(-1),
+            "H" => Some(1),
+            s if ALKALI_METALS.contains(&s) => Some(1),
+            s if ALKALINE_EARTH_METALS.contains(&s) => Some(2),
+            _ => None,
+        });
+    }
+    if unknown.len() > 1 {
+        fix(&mut unknown, &|e| match e.get_symbol() {
+            "O" => Some(-2),
+            _ => None,
+        });
+    }
+    if unknown.len() > 1 {
+        fix(&mut unknown, &|e| match e.get_symbol() {
+            "Cl" | "Br" | "I" => Some(-1),
+            _ => None,
+        });
+    }
+    let assigned: i32 = totals.values().sum();
+    match unknown.as_slice() {
+        [] if assigned == compound.charge => {}
+        [] => {
+            return Err(StoichError::InvalidInput(format!(
+                "Oxidation numbers of {} add up to {}, not its charge {}",
+                compound.formula, assigned, compound.charge
+            )))
+        }
+        [(e, _)] => {
+            totals.insert(*e, compound.charge - assigned);
+        }
+        _ => {
+            return Err(StoichError::InvalidInput(format!(
+                "Oxidation numbers of {} are ambiguous",
+                compound.formula
+            )))
+        }
+    }
+    trace!(
+        "Oxidation number totals for {}: {:?}",
+        compound.formula,
+        totals
+    );
+    let mut states = HashMap::new();
+    for (element, count) in compound.elements() {
+        let total = totals[element];
+        let n = count as i32;
+        for i in 0..n {
+            let extra = (i < total.rem_euclid(n)) as i32;
+            states.insert((*element, i as usize), total.div_euclid(n) + extra);
+        }
+    }
+    Ok(states)
+}
+
+/// The degrees of unsaturation, `(2C + 2 + N - H - X) / 2`, counting rings
+/// and pi bonds. Oxygen and other divalent elements do not contribute.
+pub fn degrees_of_unsaturation(
+    compound: &Compound,
+) -> Result<u32, StoichError> {
+    let mut total: i64 = 2;
+    for (element, count) in compound.elements() {
+        total += count as i64
+            * match element.get_symbol() {
+                "C" => 2,
+                "N" => 1,
+                "H" | "F" | "Cl" | "Br" | "I" => -1,
+                _ => 0,
+            };
+    }
+    if total < 0 || total % 2 != 0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Formula {} gives {}/2 degrees of unsaturation",
+            compound.formula, total
+        )));
+    }
+    Ok((total / 2) as u32)
+}
+
+/// Derive the empirical formula from a map of element symbol to mass
+/// percent, scaling mole ratios by up to 8 to reach whole numbers.
+pub fn empirical_formula(
+    percentages: &HashMap<String, f64>,
+) -> Result<Compound, StoichError> {
+    let total: f64 = percentages.values().sum();
+    if (total - 100.0).abs() > 5.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Percentages sum to {}, not 100",
+            total
+        )));
+    }
+    let mut moles: Vec<(Element, f64)> = vec![];
+    for (symbol, percent) in percentages {
+        if *percent <= 0.0 {
+            return Err(StoichError::InvalidInput(format!(
+                "Invalid percentage {} for {}",
+                percent, symbol
+            )));
+        }
+        let element = get_element(symbol)?;
+        moles.push((element, percent / element.get_atomic_mass()));
+    }
+    let smallest = moles.iter().map(|(_, n)| *n).fold(f64::INFINITY, f64::min);
+    if !smallest.is_finite() {
+        return Err(StoichError::InvalidInput("No elements given".to_string()));
+    }
+    trace!("Mole amounts {:?}, smallest {}", moles, smallest);
+    let multiplier = (1..=MAX_EMPIRICAL_MULTIPLIER)
+        .find(|m| {
+            moles.iter().all(|(_, n)| {
+                let ratio = n / smallest * *m as f64;
+                (ratio - ratio.round()).abs() < EMPIRICAL_TOLERANCE
+            })
+        })
+        .ok_or_else(|| {
+            StoichError::InvalidInput(format!(
+                "Mole ratios {:?} do not reduce to whole numbers",
+                moles
+            ))
+        })?;
+    debug!("Scaling mole ratios by {}", multiplier);
+    let atoms: BTreeMap<Element, u32> = moles
+        .iter()
+        .map(|(e, n)| (*e, (n / smallest * multiplier as f64).round() as u32))
+        .collect();
+    Compound::from_formula(&formula_string(&atoms))
+}
+
+/// Find the molecular formula whose mass is the whole multiple of the
+/// empirical formula nearest `molar_mass`, accepting it only when within
+/// `tolerance` (a fraction, e.g. 0.05 for 5%).
+pub fn molecular_formula(
+    empirical: &Compound,
+    molar_mass: f64,
+    tolerance: f64,
+) -> Result<Compound, StoichError> {
+    let empirical_mass = molecular_weight(empirical.atoms.clone())?;
+    let multiple = (molar_mass / empirical_mass).round();
+    if multiple < 1.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Molar mass {} is less than empirical formula mass {}",
+            molar_mass, empirical_mass
+        )));
+    }
+    let deviation = (molar_mass - multiple * empirical_mass).abs()
+        / (multiple * empirical_mass);
+    debug!("Nearest multiple {} deviates by {}", multiple, deviation);
+    if deviation > tolerance {
+        return Err(StoichError::InvalidInput(format!(
+            "Molar mass {} is not within {} of a multiple of {} ({})",
+            molar_mass, tolerance, empirical.formula, empirical_mass
+        )));
+    }
+    let atoms: BTreeMap<Element, u32> = empirical
+        .atoms
+        .iter()
+        .map(|(e, count)| (*e, count * multiple as u32))
+        .collect();
+    Compound::from_formula(&formula_string(&atoms))
+}
+
+pub(crate) fn formula_string(atoms: &BTreeMap<Element, u32>) -> String {
+    hill_order(atoms.iter().map(|(e, count)| (e, *count)))
+}
+
+// Hill order: carbon, then hydrogen, then the rest alphabetically, or all
+// alphabetically when there is no carbon. `elements` is sorted by symbol.
+pub(crate) fn hill_order<'a>(
+    elements: impl Iterator<Item = (&'a Element, u32)>,
+) -> String {
+    let mut elements: Vec<(&Element, u32)> = elements.collect();
+    if elements.iter().any(|(e, _)| e.get_symbol() == "C") {
+        elements.sort_by_key(|(e, _)| match e.get_symbol() {
+            "C" => 0,
+            "H" => 1,
+            _ => 2,
+        });
+    }
+    elements
+        .iter()
+        .map(|(e, count)| match count {
+            1 => e.notation(),
+            _ => format!("{}{}", e.notation(), count),
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
     use math::round::half_up;
 
-    use crate::molecule::molecular_weight;
+    use crate::model::Compound;
+    use crate::model::Element;
+    use crate::molecule::{
+        degrees_of_unsaturation, element_by_name, element_by_number,
+        element_by_symbol, empirical_formula, ion_mass, isotope_pattern,
+        molecular_formula, molecular_weight, molecular_weight_with,
+        molecular_weight_with_uncertainty, monoisotopic_mass, oxidation_states,
+        AtomicWeights, MolecularWeightCache,
+    };
     use crate::test_utils::e;
 
-    fn round(weight: f32) -> f64 {
-        half_up(weight as f64, 2)
+    fn round(weight: f64) -> f64 {
+        half_up(weight, 2)
     }
 
     #[test]
@@ -33,6 +571,14 @@ mod tests {
         assert_eq!(weight, 30.07);
     }
 
+    #[test]
+    fn large_polymer() {
+        let molecule: HashMap<&str, u32> =
+            [("C", 2000), ("H", 4000)].iter().cloned().collect();
+        let weight = molecular_weight(e(molecule)).unwrap();
+        assert!((weight - 28_054.0).abs() < 1e-6, "{}", weight);
+    }
+
     #[test]
     fn cellulose() {
         let molecule: HashMap<&str, u32> =
@@ -51,4 +597,254 @@ mod tests {
         let weight = molecular_weight(e(molecule)).unwrap();
         assert_eq!(round(weight), 348.27);
     }
+
+    fn percentages(p: &[(&str, f64)]) -> HashMap<String, f64> {
+        p.iter().map(|(s, v)| (s.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn empirical_glucose() {
+        let p = percentages(&[("C", 40.00), ("H", 6.71), ("O", 53.29)]);
+        assert_eq!(empirical_formula(&p).unwrap().formula, "CH2O");
+    }
+
+    #[test]
+    fn empirical_half_ratio() {
+        let p = percentages(&[("Fe", 69.94), ("O", 30.06)]);
+        assert_eq!(empirical_formula(&p).unwrap().formula, "Fe2O3");
+    }
+
+    #[test]
+    fn empirical_bad_total() {
+        let p = percentages(&[("C", 90.0), ("H", 60.0)]);
+        assert!(empirical_formula(&p)
+            .unwrap_err()
+            .to_string()
+            .contains("sum to"));
+    }
+
+    #[test]
+    fn empirical_non_convergent() {
+        let p = percentages(&[("C", 50.0), ("H", 3.2), ("O", 46.8)]);
+        let result = empirical_formula(&p);
+        assert!(result.is_err(), "{:?}", result);
+    }
+
+    #[test]
+    fn molecular_glucose() {
+        let empirical = Compound::from_formula("CH2O").unwrap();
+        let result = molecular_formula(&empirical, 180.0, 0.05).unwrap();
+        assert_eq!(result.formula, "C6H12O6");
+    }
+
+    #[test]
+    fn molecular_outside_tolerance() {
+        let empirical = Compound::from_formula("CH2O").unwrap();
+        let result = molecular_formula(&empirical, 195.0, 0.01);
+        assert!(result.unwrap_err().to_string().contains("not within"));
+        let result = molecular_formula(&empirical, 10.0, 0.05);
+        assert!(result.unwrap_err().to_string().contains("less than"));
+    }
+
+    #[test]
+    fn element_lookup() {
+        let cobalt = element_by_symbol("Co").unwrap();
+        assert_eq!(cobalt.name, "Cobalt");
+        assert_eq!(cobalt.atomic_number, 27);
+        assert_eq!(round(cobalt.atomic_weight), 58.93);
+        assert!(element_by_symbol("CO").is_err());
+        assert!(element_by_symbol("co").is_err());
+        assert_eq!(element_by_name("cOBALT").unwrap(), cobalt);
+        assert_eq!(element_by_number(27).unwrap(), cobalt);
+    }
+
+    #[test]
+    fn element_lookup_unknown() {
+        assert!(element_by_name("Unobtainium").is_err());
+        assert!(element_by_name("").is_err());
+        assert!(element_by_number(0).is_err());
+        assert!(element_by_number(119).is_err());
+    }
+
+    #[test]
+    fn glucose_monoisotopic_mass() {
+        let glucose = Compound::from_formula("C6H12O6").unwrap();
+        let mass = monoisotopic_mass(&glucose.atoms).unwrap();
+        assert!((mass - 180.0634).abs() < 1e-3, "{}", mass);
+        let xenon = Compound::from_formula("XeF4").unwrap();
+        assert!(monoisotopic_mass(&xenon.atoms).is_err());
+    }
+
+    #[test]
+    fn labelled_molecular_weight() {
+        let methane = Compound::from_formula("CH4").unwrap();
+        let labelled = Compound::from_formula("^13CH4").unwrap();
+        let shift = molecular_weight(labelled.atoms.clone()).unwrap()
+            - molecular_weight(methane.atoms).unwrap();
+        assert!((shift - 0.992).abs() < 0.01, "{}", shift);
+        let mass = monoisotopic_mass(&labelled.atoms).unwrap();
+        assert!((mass - 17.0346).abs() < 1e-3, "{}", mass);
+    }
+
+    #[test]
+    fn heavy_water() {
+        let water = Compound::from_formula("D2O").unwrap();
+        let weight = molecular_weight(water.atoms).unwrap();
+        assert!((weight - 20.03).abs() < 0.01, "{}", weight);
+    }
+
+    #[test]
+    fn chlorine_isotope_pattern() {
+        let chlorine = Compound::from_formula("Cl2").unwrap();
+        let pattern = isotope_pattern(&chlorine.atoms, 1.0).unwrap();
+        assert_eq!(pattern.len(), 3);
+        let masses: Vec<f64> = pattern.iter().map(|(m, _)| *m).collect();
+        assert!((masses[0] - 69.9377).abs() < 1e-3, "{:?}", masses);
+        assert!((masses[1] - 71.9348).abs() < 1e-3, "{:?}", masses);
+        assert!((masses[2] - 73.9318).abs() < 1e-3, "{:?}", masses);
+        let intensities: Vec<f64> = pattern.iter().map(|(_, i)| *i).collect();
+        assert_eq!(intensities[0], 100.0);
+        assert!((intensities[1] - 64.0).abs() < 0.1, "{:?}", intensities);
+        assert!((intensities[2] - 10.2).abs() < 0.1, "{:?}", intensities);
+        let pattern = isotope_pattern(&chlorine.atoms, 20.0).unwrap();
+        assert_eq!(pattern.len(), 2);
+    }
+
+    #[test]
+    fn bromine_isotope_pattern() {
+        let bromine = Compound::from_formula("Br2").unwrap();
+        let pattern = isotope_pattern(&bromine.atoms, 1.0).unwrap();
+        let masses: Vec<f64> = pattern.iter().map(|(m, _)| *m).collect();
+        assert_eq!(masses.len(), 3);
+        assert!((masses[0] - 157.8367).abs() < 1e-3, "{:?}", masses);
+        assert!((masses[1] - 159.8346).abs() < 1e-3, "{:?}", masses);
+        assert!((masses[2] - 161.8326).abs() < 1e-3, "{:?}", masses);
+        let intensities: Vec<f64> = pattern.iter().map(|(_, i)| *i).collect();
+        assert!((intensities[0] - 51.4).abs() < 0.1, "{:?}", intensities);
+        assert_eq!(intensities[1], 100.0);
+        assert!((intensities[2] - 48.6).abs() < 0.1, "{:?}", intensities);
+    }
+
+    #[test]
+    fn lead_isotope_pattern() {
+        let lead = Compound::from_formula("Pb").unwrap();
+        let pattern = isotope_pattern(&lead.atoms, 1.0).unwrap();
+        let intensities: Vec<f64> = pattern.iter().map(|(_, i)| *i).collect();
+        assert_eq!(pattern.len(), 4);
+        assert!((pattern[0].0 - 203.9730).abs() < 1e-3, "{:?}", pattern);
+        assert!((intensities[0] - 2.67).abs() < 0.01, "{:?}", intensities);
+        assert!((intensities[1] - 46.0).abs() < 0.1, "{:?}", intensities);
+        assert!((intensities[2] - 42.2).abs() < 0.1, "{:?}", intensities);
+        assert_eq!(intensities[3], 100.0);
+        let tin = Compound::from_formula("SnCl4").unwrap();
+        let pattern = isotope_pattern(&tin.atoms, 0.0).unwrap();
+        // 112Sn with four 35Cl is the lightest; nominal mass 260 is the base.
+        assert_eq!(pattern.len(), 18);
+        assert!((pattern[0].0 - 251.7802).abs() < 1e-3, "{:?}", pattern);
+        let base = pattern.iter().find(|(_, i)| *i == 100.0).unwrap();
+        assert!((base.0 - 259.775).abs() < 1e-2, "{:?}", pattern);
+    }
+
+    #[test]
+    fn electron_mass_correction() {
+        assert_eq!(ion_mass(18.0, 0), 18.0);
+        assert!((ion_mass(18.0, 1) - 17.999_451).abs() < 1e-6);
+        assert!((ion_mass(18.0, -2) - 18.001_097).abs() < 1e-6);
+    }
+
+    #[test]
+    fn weight_uncertainty() {
+        let water = Compound::from_formula("H2O").unwrap();
+        let (weight, u) =
+            molecular_weight_with_uncertainty(&water.atoms).unwrap();
+        assert!((weight - 18.015).abs() < 1e-3, "{}", weight);
+        // sqrt((2 * 0.0002)^2 + 0.001^2)
+        assert!((u - 0.001_077).abs() < 1e-6, "{}", u);
+        let labelled = Compound::from_formula("D2O").unwrap();
+        let (_, u) =
+            molecular_weight_with_uncertainty(&labelled.atoms).unwrap();
+        assert!((u - 0.001).abs() < 1e-9, "{}", u);
+        let xenon = Compound::from_formula("XeF4").unwrap();
+        assert!(molecular_weight_with_uncertainty(&xenon.atoms).is_err());
+    }
+
+    #[test]
+    fn custom_atomic_weights() {
+        let water = Compound::from_formula("H2O").unwrap();
+        let mut weights = AtomicWeights::default();
+        assert_eq!(
+            molecular_weight_with(water.atoms.clone(), &weights).unwrap(),
+            molecular_weight(water.atoms.clone()).unwrap()
+        );
+        weights.set("H", 2.014).unwrap();
+        let heavy = molecular_weight_with(water.atoms, &weights).unwrap();
+        assert!((heavy - 20.027).abs() < 1e-3, "{}", heavy);
+        assert!(weights.set("Xx", 1.0).is_err());
+        assert!(weights.set("O", -1.0).is_err());
+    }
+
+    fn oxidation(formula: &str, symbol: &str) -> Vec<i32> {
+        let compound = Compound::from_formula(formula).unwrap();
+        let states = oxidation_states(&compound).unwrap();
+        let element = Element::from_symbol(symbol).unwrap();
+        let mut found: Vec<i32> = states
+            .iter()
+            .filter(|((e, _), _)| *e == element)
+            .map(|(_, state)| *state)
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn oxidation_numbers() {
+        assert_eq!(oxidation("H2O", "H"), vec![1, 1]);
+        assert_eq!(oxidation("H2O", "O"), vec![-2]);
+        assert_eq!(oxidation("H2O2", "O"), vec![-1, -1]);
+        assert_eq!(oxidation("KMnO4", "Mn"), vec![7]);
+        assert_eq!(oxidation("KMnO4", "K"), vec![1]);
+        assert_eq!(oxidation("NaH", "H"), vec![-1]);
+        assert_eq!(oxidation("OF2", "O"), vec![2]);
+        assert_eq!(oxidation("Cr2O7^2-", "Cr"), vec![6, 6]);
+        assert_eq!(oxidation("ClO^-", "Cl"), vec![1]);
+        assert_eq!(oxidation("Fe3O4", "Fe"), vec![2, 3, 3]);
+        assert_eq!(oxidation("O2", "O"), vec![0, 0]);
+        assert_eq!(oxidation("NH4+", "N"), vec![-3]);
+    }
+
+    #[test]
+    fn oxidation_numbers_ambiguous() {
+        let pyrite = Compound::from_formula("FeS2").unwrap();
+        let err = oxidation_states(&pyrite).unwrap_err();
+        assert!(err.to_string().contains("ambiguous"), "{}", err);
+    }
+
+    #[test]
+    fn unsaturation() {
+        let degrees = |formula: &str| {
+            degrees_of_unsaturation(&Compound::from_formula(formula).unwrap())
+        };
+        assert_eq!(degrees("C6H6").unwrap(), 4);
+        assert_eq!(degrees("C6H14").unwrap(), 0);
+        assert_eq!(degrees("C5H5N").unwrap(), 4);
+        assert_eq!(degrees("C2H3Cl").unwrap(), 1);
+        assert_eq!(degrees("C6H12O6").unwrap(), 1);
+        assert!(degrees("C6H7").is_err());
+        assert!(degrees("CH6").is_err());
+    }
+
+    #[test]
+    fn weight_cache() {
+        let mut cache = MolecularWeightCache::new();
+        assert!(cache.is_empty());
+        let water = cache.get("H2O").unwrap();
+        assert!((water - 18.015).abs() < 1e-3, "{}", water);
+        assert_eq!(cache.get("H2O").unwrap(), water);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get("Xx").is_err());
+        assert_eq!(cache.len(), 1);
+        // Keyed as written, not by composition.
+        assert!((cache.get("HOH").unwrap() - water).abs() < 1e-9);
+        assert_eq!(cache.len(), 2);
+    }
 }
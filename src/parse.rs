@@ -1,62 +1,429 @@
-use std::collections::hash_map::RandomState;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::panic;
 
-use crate::model::Element;
+use crate::error::StoichError;
+use crate::model::{Compound, Element, State};
 
-fn get_element(symbol: &str) -> Result<Element, String> {
+// Equation terms with their leading coefficients, if written.
+type Terms = Vec<(Compound, Option<u32>)>;
+
+// Reversible arrows come first, since `<=>` and `<->` contain the others.
+const ARROWS: [&str; 6] = ["<=>", "<->", "⇌", "->", "→", "="];
+const REVERSIBLE_ARROWS: [&str; 3] = ["<=>", "<->", "⇌"];
+const HYDRATE_SEPARATORS: [char; 3] = ['·', '*', '.'];
+const SUPERSCRIPTS: [char; 10] =
+    ['⁰', '¹', '²', '³', '⁴', '⁵', '⁶', '⁷', '⁸', '⁹'];
+// Organic shorthand. Pr and Ts are left out as they are element symbols;
+// Ac is included, so actinium cannot be written once abbreviations are on.
+const ABBREVIATIONS: [(&str, &str); 15] = [
+    ("Me", "CH3"),
+    ("Et", "C2H5"),
+    ("nPr", "C3H7"),
+    ("iPr", "C3H7"),
+    ("nBu", "C4H9"),
+    ("iBu", "C4H9"),
+    ("sBu", "C4H9"),
+    ("tBu", "C4H9"),
+    ("Bu", "C4H9"),
+    ("Ph", "C6H5"),
+    ("Bn", "C7H7"),
+    ("Bz", "C7H5O"),
+    ("Cy", "C6H11"),
+    ("Ac", "C2H3O"),
+    ("Ms", "CH3SO2"),
+];
+pub(crate) const SUBSCRIPTS: [char; 10] =
+    ['₀', '₁', '₂', '₃', '₄', '₅', '₆', '₇', '₈', '₉'];
+
+pub(crate) fn get_element(symbol: &str) -> Result<Element, StoichError> {
+    let unknown = || StoichError::UnknownElement(symbol.to_string());
     match symbol.chars().all(|c| c.is_ascii_alphabetic()) {
         true => {
             let e: Result<Option<Element>, _> =
                 panic::catch_unwind(|| Element::from_symbol(symbol));
-            e.unwrap_or(None)
-                .ok_or(format!("Invalid symbol {}", symbol))
+            e.unwrap_or(None).ok_or_else(unknown)
         }
-        false => Err(format!("Invalid symbol {}", symbol)),
+        false => Err(unknown()),
     }
 }
 
-pub fn get_element_by_id(id: u8) -> Result<Element, String> {
+pub fn get_element_by_id(id: u8) -> Result<Element, StoichError> {
     let e: Result<Option<Element>, _> =
         panic::catch_unwind(|| Element::from_atomic_number(id.into()));
     e.unwrap_or(None)
-        .ok_or(format!("Invalid atomic number {}", id))
+        .ok_or(StoichError::InvalidAtomicNumber(id))
 }
 
-// translated from https://leetcode.com/articles/number-of-atoms/#
+// `D` and `T` are shorthand for deuterium and tritium; no element uses
+// either as its symbol.
+fn hydrogen_isotope(symbol: &str) -> Option<Element> {
+    let mass_number = match symbol {
+        "D" => 2,
+        "T" => 3,
+        _ => return None,
+    };
+    Element::from_symbol("H")?.isotope(mass_number)
+}
+
+fn charge_value(token: &str) -> Option<i32> {
+    let digits = token.chars().take_while(char::is_ascii_digit).count();
+    let signs = &token[digits..];
+    let sign = match signs.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    if !signs.chars().all(|c| signs.starts_with(c)) {
+        return None;
+    }
+    match (digits, signs.len()) {
+        (0, n) => Some(sign * n as i32),
+        (_, 1) => token[..digits]
+            .parse::<i32>()
+            .ok()
+            .filter(|m| *m > 0)
+            .map(|m| sign * m),
+        _ => None,
+    }
+}
+
+/// Split a trailing ionic charge such as `^2-`, `+`, `--` or `3+` off a
+/// formula, returning the neutral formula and the net charge. Without a
+/// `^`, a count directly before a single sign is read as the charge only
+/// for a lone element (`Fe3+`), otherwise it stays a subscript (`NH4+`).
+pub fn parse_charge(formula: &str) -> Result<(&str, i32), StoichError> {
+    let caret = formula
+        .rfind('^')
+        .filter(|i| !is_isotope_label(&formula[i + 1..]));
+    let (body, token) = match caret {
+        Some(i) => (&formula[..i], &formula[i + 1..]),
+        None => {
+            let unsigned = formula.trim_end_matches(['+', '-']);
+            let signs = formula.len() - unsigned.len();
+            if signs == 0 {
+                return Ok((formula, 0));
+            }
+            let symbol =
+                unsigned.trim_end_matches(|c: char| c.is_ascii_digit());
+            let monatomic = signs == 1
+                && symbol.len() < unsigned.len()
+                && get_element(symbol).is_ok();
+            match monatomic {
+                true => (symbol, &formula[symbol.len()..]),
+                false => (unsigned, &formula[unsigned.len()..]),
+            }
+        }
+    };
+    if body.is_empty() {
+        return Err(StoichError::parse(
+            0,
+            format!("Missing formula before charge in {:?}", formula),
+        ));
+    }
+    let charge = charge_value(token).ok_or_else(|| {
+        StoichError::parse(
+            formula.len() - token.len(),
+            format!("Malformed charge {:?} in formula {}", token, formula),
+        )
+    })?;
+    trace!("Got charge {:?} for formula {:?}", charge, body);
+    Ok((body, charge))
+}
+
+// Replaces Unicode subscript digits with their ASCII equivalents, and a
+// superscript mass number before an element (`¹³C`) with `^13C`.
+fn normalize_digits(formula: &str) -> String {
+    let digit = |table: &[char; 10], c: char| {
+        table
+            .iter()
+            .position(|s| *s == c)
+            .map(|d| (b'0' + d as u8) as char)
+    };
+    let mut normalized = String::new();
+    let mut chars = formula.chars().peekable();
+    while let Some(c) = chars.next() {
+        if digit(&SUPERSCRIPTS, c).is_none() {
+            normalized.push(digit(&SUBSCRIPTS, c).unwrap_or(c));
+            continue;
+        }
+        let mut run = vec![c];
+        while let Some(next) = chars.next_if(|n| SUPERSCRIPTS.contains(n)) {
+            run.push(next);
+        }
+        match chars.peek() {
+            Some(next) if next.is_ascii_uppercase() => {
+                normalized.push('^');
+                normalized.extend(
+                    run.iter().filter_map(|c| digit(&SUPERSCRIPTS, *c)),
+                );
+            }
+            _ => normalized.extend(run),
+        }
+    }
+    normalized
+}
+
+// Digits followed by an element symbol, as in `^13C`.
+fn is_isotope_label(token: &str) -> bool {
+    let symbol = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    symbol.len() < token.len()
+        && symbol.starts_with(|c: char| c.is_ascii_uppercase())
+}
+
+/// Replace organic abbreviations such as `Me` and `Ph` with their
+/// bracketed formulas, so `MeOH` becomes `(CH3)OH`. Anything else,
+/// including element symbols, is left as written.
+pub fn expand_abbreviations(formula: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest = formula;
+    let mut prev: Option<char> = None;
+    while let Some(c) = rest.chars().next() {
+        // A lowercase letter after an uppercase one belongs to a symbol.
+        let boundary = c.is_ascii_uppercase()
+            || (c.is_ascii_lowercase()
+                && !prev.is_some_and(|p| p.is_ascii_uppercase()));
+        let found = ABBREVIATIONS.iter().find(|(short, _)| {
+            boundary
+                && rest.starts_with(short)
+                && !rest[short.len()..]
+                    .starts_with(|n: char| n.is_ascii_lowercase())
+        });
+        match found {
+            Some((short, full)) => {
+                trace!("Expanding {} to {}", short, full);
+                expanded.push_str(&format!("({})", full));
+                rest = &rest[short.len()..];
+                prev = Some(')');
+            }
+            None => {
+                expanded.push(c);
+                rest = &rest[c.len_utf8()..];
+                prev = Some(c);
+            }
+        }
+    }
+    expanded
+}
+
+/// Split a trailing state symbol such as `(aq)` off a formula.
+pub fn parse_state(formula: &str) -> (&str, Option<State>) {
+    State::ALL
+        .iter()
+        .find_map(|state| {
+            formula
+                .strip_suffix(state.symbol())
+                .map(|body| (body.trim_end(), Some(*state)))
+        })
+        .unwrap_or((formula, None))
+}
+
+/// Count the atoms in a formula. Symbols are case-sensitive: a capital
+/// letter and any lowercase letters after it, so `Co` is cobalt and `CO`
+/// is carbon and oxygen. Any other casing is an unknown element.
 pub fn parse_formula(
     formula: &str,
-) -> Result<HashMap<Element, u32, RandomState>, String> {
-    let mut stack: Vec<HashMap<Element, u32>> = vec![HashMap::new()];
+) -> Result<BTreeMap<Element, u32>, StoichError> {
+    let normalized = normalize_digits(formula);
+    let (formula, _) = parse_charge(&normalized)?;
+    if formula.trim().is_empty() {
+        return Err(StoichError::parse(0, "Formula is empty".to_string()));
+    }
+    if let Some(position) = ARROWS
+        .iter()
+        .chain(["+"].iter())
+        .filter_map(|token| formula.find(token))
+        .min()
+    {
+        return Err(StoichError::parse(
+            position,
+            format!(
+                "Formula {:?} looks like an equation, use parse_equation \
+                 to parse it",
+                formula
+            ),
+        ));
+    }
+    if let Some(position) = repeat_count(formula) {
+        return Err(StoichError::parse(
+            position,
+            format!(
+                "Formula {:?} has an indefinite repeat count, use \
+                 repeat_unit to parse its repeat unit",
+                formula
+            ),
+        ));
+    }
+    let mut segments = formula.split(&HYDRATE_SEPARATORS[..]);
+    let first = segments.next().unwrap_or("");
+    let mut atoms = parse_segment(first)?;
+    let mut offset = first.len();
+    for segment in segments {
+        offset += formula[offset..].chars().next().map_or(0, char::len_utf8);
+        let start = offset;
+        offset += segment.len();
+        let digits = segment.chars().take_while(char::is_ascii_digit).count();
+        let mult: u32 = match digits {
+            0 => 1,
+            _ => match segment[..digits].parse::<u32>() {
+                Ok(mult) if mult > 0 => mult,
+                _ => {
+                    return Err(StoichError::parse(
+                        start,
+                        format!(
+                            "Invalid hydrate multiplicity in {:?}",
+                            segment
+                        ),
+                    ))
+                }
+            },
+        };
+        if segment[digits..].is_empty() {
+            return Err(StoichError::parse(
+                start,
+                format!(
+                    "Empty hydrate segment {:?} in formula {}",
+                    segment, formula
+                ),
+            ));
+        }
+        let hydrate = parse_segment(&segment[digits..]).map_err(|e| {
+            StoichError::parse(
+                start + digits + e.position().unwrap_or(0),
+                format!(
+                    "Invalid hydrate segment {:?} in formula {}: {}",
+                    segment, formula, e
+                ),
+            )
+        })?;
+        trace!("Got hydrate {:?} x {:?}", mult, hydrate);
+        for (elem, count) in hydrate {
+            let total = atoms.entry(elem).or_insert(0);
+            *total = add_atoms(*total, count, mult)?;
+        }
+    }
+    Ok(atoms)
+}
+
+// The position of a letter multiplier after a closing bracket at the end of
+// a formula, as in `(C2H4)n`.
+fn repeat_count(formula: &str) -> Option<usize> {
+    let mut chars = formula.char_indices().rev();
+    match (chars.next(), chars.next()) {
+        (Some((position, letter)), Some((_, close)))
+            if letter.is_ascii_lowercase() && ")]}".contains(close) =>
+        {
+            Some(position)
+        }
+        _ => None,
+    }
+}
+
+/// Parse the repeat unit of a polymer written as `(C2H4)n`, returning the
+/// monomer `C2H4`.
+pub fn repeat_unit(formula: &str) -> Result<Compound, StoichError> {
+    let formula = formula.trim();
+    let position = repeat_count(formula).ok_or_else(|| {
+        StoichError::parse(
+            formula.len(),
+            format!("Formula {:?} has no repeat count", formula),
+        )
+    })?;
+    let group = &formula[..position];
+    // Drop the brackets only when they enclose the whole unit.
+    let mut depth = 0;
+    let enclosed = group.char_indices().all(|(i, c)| {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            _ => {}
+        }
+        depth > 0 || i == group.len() - 1
+    });
+    let unit = match enclosed {
+        true => &group[1..group.len() - 1],
+        false => group,
+    };
+    Compound::from_formula(unit).map_err(|e| match e.position() {
+        Some(p) if enclosed => StoichError::parse(p + 1, e.to_string()),
+        _ => e,
+    })
+}
+
+// The count written at `formula[start..end]`, or 1 when there is none.
+fn subscript(
+    formula: &str,
+    start: usize,
+    end: usize,
+) -> Result<u32, StoichError> {
+    if start == end {
+        return Ok(1);
+    }
+    let digits = &formula[start..end];
+    match digits.parse::<u32>() {
+        Ok(0) => Err(StoichError::parse(
+            start,
+            format!(
+                "Zero subscript at position {} in formula {}",
+                start, formula
+            ),
+        )),
+        Ok(count) => Ok(count),
+        Err(_) => Err(StoichError::Overflow(digits.to_string())),
+    }
+}
+
+// `total` plus `count` atoms repeated `mult` times.
+fn add_atoms(total: u32, count: u32, mult: u32) -> Result<u32, StoichError> {
+    count
+        .checked_mul(mult)
+        .and_then(|added| total.checked_add(added))
+        .ok_or_else(|| {
+            StoichError::Overflow(
+                (total as u64 + count as u64 * mult as u64).to_string(),
+            )
+        })
+}
+
+// translated from https://leetcode.com/articles/number-of-atoms/#
+fn parse_segment(formula: &str) -> Result<BTreeMap<Element, u32>, StoichError> {
+    let mut stack: Vec<BTreeMap<Element, u32>> = vec![BTreeMap::new()];
+    let mut openings: Vec<(char, usize)> = vec![];
     let mut i: usize = 0;
     let formula_len: usize = formula.len();
     let mut broken: bool = false;
+    // `i` is a byte offset. Every token it steps over is ASCII, so it stays
+    // on a character boundary until an invalid character stops the loop.
+    let char_at = |i: usize| formula[i..].chars().next();
     trace!("Parsing formula {:?}", formula);
     while i < formula_len && !broken {
-        match formula.chars().nth(i).unwrap() {
-            '(' | '[' | '{' => {
+        match char_at(i).unwrap() {
+            open @ '(' | open @ '[' | open @ '{' => {
                 trace!("Start of group at {:?}", i);
-                stack.push(HashMap::new());
+                stack.push(BTreeMap::new());
+                openings.push((open, i));
                 i += 1;
             }
-            ')' | ']' | '}' => {
+            close @ ')' | close @ ']' | close @ '}' => {
+                if stack.len() < 2 {
+                    return Err(StoichError::parse(
+                        i,
+                        format!(
+                            "Unmatched {:?} at position {} in formula {}",
+                            close, i, formula
+                        ),
+                    ));
+                }
+                openings.pop();
                 match stack.pop() {
                     Some(top) => {
                         i += 1;
                         let i_start = i;
                         while i < formula_len
-                            && formula.chars().nth(i).unwrap().is_digit(10)
+                            && char_at(i).unwrap().is_ascii_digit()
                         {
                             i += 1;
                         }
-                        let mult: u32 = match i_start == i {
-                            true => 1,
-                            false => {
-                                let multiplicity_str =
-                                    formula.get(i_start..i).unwrap();
-                                multiplicity_str.parse::<u32>().unwrap()
-                            }
-                        };
+                        let mult = subscript(formula, i_start, i)?;
                         trace!(
                             "Got multiplicity {:?} for group {:?}",
                             mult,
@@ -73,8 +440,7 @@ pub fn parse_formula(
                                             .insert(elem, 0)
                                             .unwrap_or(0),
                                     };
-                                    let addt = v * mult;
-                                    let new = curr + addt;
+                                    let new = add_atoms(curr, v, mult)?;
                                     stack.last_mut().unwrap().insert(elem, new);
                                 }
                                 None => broken = true,
@@ -84,30 +450,105 @@ pub fn parse_formula(
                     None => broken = true,
                 };
             }
-            token if token.is_ascii_alphanumeric() => {
+            token if token.is_ascii_alphanumeric() || token == '^' => {
+                let label_start = i;
+                if token == '^' {
+                    i += 1;
+                }
+                let digits_start = i;
+                while i < formula_len && char_at(i).unwrap().is_ascii_digit() {
+                    i += 1;
+                }
+                let mass_number = match (token, digits_start == i) {
+                    (_, false) => Some(
+                        formula[digits_start..i].parse::<u16>().map_err(|_| {
+                            StoichError::parse(
+                                digits_start,
+                                format!(
+                                    "Invalid mass number at position {} in formula {}",
+                                    digits_start, formula
+                                ),
+                            )
+                        })?,
+                    ),
+                    ('^', true) => {
+                        return Err(StoichError::parse(
+                            label_start,
+                            format!(
+                                "Missing mass number at position {} in formula {}",
+                                label_start, formula
+                            ),
+                        ))
+                    }
+                    _ => None,
+                };
+                let before_element =
+                    char_at(i).is_some_and(|c| c.is_ascii_uppercase());
+                if token != '^' && mass_number.is_some() && !before_element {
+                    return Err(StoichError::parse(
+                        label_start,
+                        format!(
+                            "Subscript at position {} in formula {} does not follow an element or group",
+                            label_start, formula
+                        ),
+                    ));
+                }
+                if mass_number.is_some() && !before_element {
+                    return Err(StoichError::parse(
+                        label_start,
+                        format!(
+                            "Isotope label at position {} in formula {} is not followed by an element",
+                            label_start, formula
+                        ),
+                    ));
+                }
                 let mut i_start = i;
                 i += 1;
                 while i < formula_len
-                    && formula.chars().nth(i).unwrap().is_lowercase()
+                    && char_at(i).unwrap().is_ascii_lowercase()
                 {
                     i += 1;
                 }
                 let name = formula.get(i_start..i).unwrap();
                 i_start = i;
                 trace!("Captured symbol {:?}", name);
-                let elem: Element = get_element(name)?;
-                while i < formula_len
-                    && formula.chars().nth(i).unwrap().is_digit(10)
-                {
+                let elem = match hydrogen_isotope(name) {
+                    Some(isotope) => Ok(isotope),
+                    None => get_element(name),
+                };
+                let elem: Element = elem.map_err(|_| {
+                    let hint = suggest_symbols(name)
+                        .map(|s| format!(", did you mean {}?", s))
+                        .unwrap_or_default();
+                    StoichError::parse(
+                        i_start - name.len(),
+                        format!(
+                            "Unknown element {:?} at position {} in formula {}{}",
+                            name,
+                            i_start - name.len(),
+                            formula,
+                            hint
+                        ),
+                    )
+                })?;
+                let elem = match mass_number {
+                    Some(n) => elem.isotope(n).ok_or_else(|| {
+                        StoichError::parse(
+                            label_start,
+                            format!(
+                                "Unknown isotope {} at position {} in formula {}",
+                                &formula[label_start..i_start],
+                                label_start,
+                                formula
+                            ),
+                        )
+                    })?,
+                    None => elem,
+                };
+                while i < formula_len && char_at(i).unwrap().is_ascii_digit() {
                     i += 1
                 }
-                let mult: u32 = match i_start == i {
-                    true => 1,
-                    false => {
-                        let multiplicity_str = formula.get(i_start..i).unwrap();
-                        multiplicity_str.parse::<u32>().unwrap()
-                    }
-                };
+                let mult = subscript(formula, i_start, i)?;
                 trace!("Got multiplicity {:?} for element {:?}", mult, name);
                 match stack.last() {
                     Some(last) => {
@@ -119,40 +560,291 @@ pub fn parse_formula(
                                 .insert(elem, 0)
                                 .unwrap_or(0),
                         };
-                        let new = curr.to_owned() + mult;
+                        let new = add_atoms(curr, mult, 1)?;
                         stack.last_mut().unwrap().insert(elem, new);
                     }
                     None => broken = true,
                 }
             }
             invalid => {
-                error!(
-                    "Got invalid character {:?} in formula {}",
-                    invalid, formula
-                );
-                broken = true;
+                return Err(StoichError::parse(
+                    i,
+                    format!(
+                        "Invalid character {:?} at position {} in formula {}",
+                        invalid, i, formula
+                    ),
+                ));
             }
         }
     }
 
     if broken {
-        Err(String::from("Could not parse"))
+        Err(StoichError::parse(i, String::from("Could not parse")))
+    } else if let Some((open, position)) = openings.last() {
+        Err(StoichError::parse(
+            *position,
+            format!(
+                "Unclosed {:?} at position {} in formula {}",
+                open, position, formula
+            ),
+        ))
     } else {
         let result = stack
             .last()
-            .ok_or(format!("Error parsing {}", formula))?
+            .ok_or_else(|| {
+                StoichError::parse(0, format!("Error parsing {}", formula))
+            })?
             .to_owned();
         Ok(result)
     }
 }
 
+fn symbols() -> impl Iterator<Item = &'static str> {
+    (1..=118)
+        .filter_map(|z| Element::from_atomic_number(z).map(|e| e.get_symbol()))
+}
+
+fn capitalize(token: &str) -> String {
+    let mut chars = token.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+// Reads a run like `Clo` as consecutive symbols (`Cl`, `O`), preferring
+// two-letter symbols.
+fn split_symbols(token: &str) -> Option<Vec<&'static str>> {
+    if token.is_empty() {
+        return Some(vec![]);
+    }
+    (1..=token.len().min(2)).rev().find_map(|k| {
+        let head = capitalize(token.get(..k)?);
+        let symbol = symbols().find(|s| *s == head)?;
+        let mut rest = split_symbols(&token[k..])?;
+        rest.insert(0, symbol);
+        Some(rest)
+    })
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != *cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Suggests what an unknown symbol was probably meant to be: the same symbol
+// in another case, several run-together symbols, or else the nearest
+// symbol by edit distance.
+fn suggest_symbols(token: &str) -> Option<String> {
+    if let Some(symbol) = symbols().find(|s| s.eq_ignore_ascii_case(token)) {
+        return Some(symbol.to_string());
+    }
+    match split_symbols(token) {
+        Some(parts) if parts.len() > 1 => Some(parts.join(" + ")),
+        _ => symbols()
+            .min_by_key(|s| edit_distance(token, s))
+            .map(str::to_string),
+    }
+}
+
+// A `+` directly after a formula and followed by whitespace, another sign,
+// a state symbol or the end of the side is an ionic charge rather than a
+// separator.
+fn split_terms(side: &str) -> Vec<(usize, &str)> {
+    let mut terms = vec![];
+    let mut start = 0;
+    let mut prev: Option<char> = None;
+    let mut chars = side.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, n)| *n);
+        let charge = match (prev, next) {
+            (Some('^'), _) => true,
+            (Some(p), n) if !p.is_whitespace() => match n {
+                None => true,
+                Some(n) => {
+                    n.is_whitespace()
+                        || n == '+'
+                        || n == '-'
+                        || State::ALL
+                            .iter()
+                            .any(|s| side[i + 1..].starts_with(s.symbol()))
+                }
+            },
+            _ => false,
+        };
+        if c == '+' && !charge {
+            terms.push((start, &side[start..i]));
+            start = i + c.len_utf8();
+        }
+        prev = Some(c);
+    }
+    terms.push((start, &side[start..]));
+    terms
+}
+
+fn parse_equation_side(
+    side: &str,
+    offset: usize,
+    name: &str,
+    equation: &str,
+    coefficients: bool,
+) -> Result<Terms, StoichError> {
+    if side.trim().is_empty() {
+        return Err(StoichError::parse(
+            offset,
+            format!("No {} in equation {:?}", name, equation),
+        ));
+    }
+    split_terms(side)
+        .into_iter()
+        .map(|(start, term)| {
+            let trimmed = term.trim();
+            let formula = trimmed
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .trim_start();
+            let start = offset + start + term.len() - term.trim_start().len();
+            if formula.is_empty() {
+                return Err(StoichError::parse(
+                    start,
+                    format!(
+                        "Empty term {:?} in {} of equation {:?}",
+                        term, name, equation
+                    ),
+                ));
+            }
+            trace!("Captured {} term {:?}", name, formula);
+            let skipped = trimmed.len() - formula.len();
+            let digits = trimmed[..skipped].trim_end();
+            let coefficient = match digits {
+                _ if !coefficients => None,
+                "" => None,
+                digits => match digits.parse::<u32>() {
+                    Ok(n) if n > 0 => Some(n),
+                    _ => {
+                        return Err(StoichError::parse(
+                            start,
+                            format!(
+                            "Invalid coefficient {:?} in {} of equation {:?}",
+                            digits, name, equation
+                        ),
+                        ))
+                    }
+                },
+            };
+            let compound = Compound::from_formula(formula).map_err(|e| {
+                StoichError::parse(
+                    start + skipped + e.position().unwrap_or(0),
+                    format!("Invalid {} term {:?}: {}", name, trimmed, e),
+                )
+            })?;
+            Ok((compound, coefficient))
+        })
+        .collect()
+}
+
+/// Parse a full chemical equation such as `2 H2 + O2 -> H2O` into its
+/// reagents and products. Any of `=`, `->`, `→` or the equilibrium arrows
+/// `<=>`, `<->` and `⇌` may separate the sides, and leading coefficients
+/// on each term are discarded. Use `parse_reaction` to also learn which
+/// kind of arrow it was.
+pub fn parse_equation(
+    equation: &str,
+) -> Result<(Vec<Compound>, Vec<Compound>), StoichError> {
+    let (reagents, products) = split_equation(equation, false)?;
+    let compounds = |side: Terms| -> Vec<Compound> {
+        side.into_iter().map(|(compound, _)| compound).collect()
+    };
+    Ok((compounds(reagents), compounds(products)))
+}
+
+/// Like `parse_equation`, but also returns whether the equation is written
+/// with an equilibrium arrow.
+pub fn parse_reaction(
+    equation: &str,
+) -> Result<(Vec<Compound>, Vec<Compound>, bool), StoichError> {
+    let (reagents, products) = parse_equation(equation)?;
+    Ok((reagents, products, is_reversible(equation)))
+}
+
+/// Like `parse_equation`, but keeps each term's leading coefficient, or
+/// `None` where it has none.
+pub fn parse_equation_with_coefficients(
+    equation: &str,
+) -> Result<(Terms, Terms), StoichError> {
+    split_equation(equation, true)
+}
+
+/// Whether `equation` is written with an equilibrium arrow: `<=>`, `<->`
+/// or `⇌`.
+pub fn is_reversible(equation: &str) -> bool {
+    ARROWS
+        .iter()
+        .find(|a| equation.contains(*a))
+        .is_some_and(|a| REVERSIBLE_ARROWS.contains(a))
+}
+
+fn split_equation(
+    equation: &str,
+    coefficients: bool,
+) -> Result<(Terms, Terms), StoichError> {
+    let arrow =
+        ARROWS
+            .iter()
+            .find(|a| equation.contains(*a))
+            .ok_or_else(|| {
+                StoichError::parse(
+                    equation.len(),
+                    format!("No reaction arrow in equation {:?}", equation),
+                )
+            })?;
+    let mut sides = equation.splitn(2, arrow);
+    let lhs = sides.next().unwrap_or("");
+    let rhs = sides.next().unwrap_or("");
+    let rhs_offset = lhs.len() + arrow.len();
+    if let Some(extra) = ARROWS.iter().find(|a| rhs.contains(*a)) {
+        return Err(StoichError::parse(
+            rhs_offset + rhs.find(extra).unwrap_or(0),
+            format!(
+                "Unexpected second arrow {:?} in equation {:?}",
+                extra, equation
+            ),
+        ));
+    }
+    debug!("Parsing equation {:?} split at {:?}", equation, arrow);
+    let reagents =
+        parse_equation_side(lhs, 0, "reagents", equation, coefficients)?;
+    let products = parse_equation_side(
+        rhs,
+        rhs_offset,
+        "products",
+        equation,
+        coefficients,
+    )?;
+    Ok((reagents, products))
+}
+
 #[cfg(test)]
 mod tests {
-    use std::collections::hash_map::RandomState;
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
+    use crate::error::StoichError;
+    use crate::model::Compound;
     use crate::model::Element;
-    use crate::parse::parse_formula;
+    use crate::parse::{
+        expand_abbreviations, is_reversible, parse_charge, parse_equation,
+        parse_equation_with_coefficients, parse_formula, parse_reaction,
+        repeat_unit,
+    };
     use crate::test_utils::e;
 
     #[test]
@@ -176,25 +868,103 @@ mod tests {
         assert_eq!(result, e(expected));
     }
 
+    #[test]
+    fn calcium_hydroxide() {
+        let result = parse_formula("Ca(OH)2").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("Ca", 1), ("O", 2), ("H", 2)].iter().cloned().collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn aluminium_sulfate() {
+        let result = parse_formula("Al2(SO4)3").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("Al", 2), ("S", 3), ("O", 12)].iter().cloned().collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn tetraamminecopper() {
+        let result = parse_formula("Cu(NH3)4").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("Cu", 1), ("N", 4), ("H", 12)].iter().cloned().collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn potassium_ferrocyanide() {
+        let result = parse_formula("K4[Fe(CN)6]").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("K", 4), ("Fe", 1), ("C", 6), ("N", 6)]
+                .iter()
+                .cloned()
+                .collect();
+        assert_eq!(result, e(expected));
+    }
+
     #[test]
     fn missing_open_bracket() {
         let formula = "(C2H3)3)";
         let result = parse_formula(formula);
         assert!(result.is_err(), "{:?}", result.ok());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unmatched ')' at position 7"));
     }
 
     #[test]
-    #[should_panic] // todo: better enforce matching brakcets
     fn missing_closing_bracket() {
         let formula = "((C2H3)3";
         let result = parse_formula(formula);
         assert!(result.is_err(), "{:?}", result.ok());
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unclosed '(' at position 0"));
+    }
+
+    #[test]
+    fn tetraamminecopper_sulfate() {
+        let result = parse_formula("[Cu(NH3)4]SO4").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("Cu", 1), ("N", 4), ("H", 12), ("S", 1), ("O", 4)]
+                .iter()
+                .cloned()
+                .collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn potassium_ferricyanide() {
+        let result = parse_formula("K3[Fe(CN)6]").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("K", 3), ("Fe", 1), ("C", 6), ("N", 6)]
+                .iter()
+                .cloned()
+                .collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn unmatched_square_brackets() {
+        let result = parse_formula("[Cu(NH3)4SO4");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unclosed '[' at position 0"));
+        let result = parse_formula("Cu(NH3)4]SO4");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Unmatched ']' at position 8"));
     }
 
     #[test]
     fn mismatched_bracket_types() {
         let formula: &str = "{C2H6)12";
-        let result: HashMap<Element, u32, RandomState> =
+        let result: BTreeMap<Element, u32> =
             parse_formula(formula).ok().unwrap();
         let expected: HashMap<&str, u32> =
             [("C", 24), ("H", 72)].iter().cloned().collect();
@@ -214,4 +984,368 @@ mod tests {
         let result = parse_formula(formula);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn copper_sulfate_pentahydrate() {
+        let expected: HashMap<&str, u32> =
+            [("Cu", 1), ("S", 1), ("O", 9), ("H", 10)]
+                .iter()
+                .cloned()
+                .collect();
+        for formula in &["CuSO4·5H2O", "CuSO4*5H2O", "CuSO4.5H2O"] {
+            assert_eq!(parse_formula(formula).unwrap(), e(expected.clone()));
+        }
+    }
+
+    #[test]
+    fn multiple_hydrate_segments() {
+        let result = parse_formula("Na2CO3.10H2O·NH3").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("Na", 2), ("C", 1), ("O", 13), ("H", 23), ("N", 1)]
+                .iter()
+                .cloned()
+                .collect();
+        assert_eq!(result, e(expected));
+    }
+
+    #[test]
+    fn invalid_hydrate_segment() {
+        let result = parse_formula("CuSO4·5Xq2O");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid hydrate segment"));
+        let result = parse_formula("CuSO4·5");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Empty hydrate segment"));
+    }
+
+    #[test]
+    fn charge_with_caret() {
+        assert_eq!(parse_charge("SO4^2-").unwrap(), ("SO4", -2));
+        assert_eq!(parse_charge("MnO4^-").unwrap(), ("MnO4", -1));
+        assert_eq!(parse_charge("Fe^3+").unwrap(), ("Fe", 3));
+        assert_eq!(parse_charge("SO4^--").unwrap(), ("SO4", -2));
+    }
+
+    #[test]
+    fn charge_without_caret() {
+        assert_eq!(parse_charge("Na+").unwrap(), ("Na", 1));
+        assert_eq!(parse_charge("Fe3+").unwrap(), ("Fe", 3));
+        assert_eq!(parse_charge("NH4+").unwrap(), ("NH4", 1));
+        assert_eq!(parse_charge("SO4--").unwrap(), ("SO4", -2));
+        assert_eq!(parse_charge("H2O").unwrap(), ("H2O", 0));
+    }
+
+    #[test]
+    fn equation_arrows() {
+        for (equation, reversible) in [
+            ("N2 + 3 H2 = 2 NH3", false),
+            ("N2 + 3 H2 -> 2 NH3", false),
+            ("N2 + 3 H2 → 2 NH3", false),
+            ("N2 + 3 H2 <=> 2 NH3", true),
+            ("N2 + 3 H2 ⇌ 2 NH3", true),
+            ("N2 + 3 H2 <-> 2 NH3", true),
+        ] {
+            let (reagents, products) = parse_equation(equation).unwrap();
+            assert_eq!(reagents.len(), 2, "{}", equation);
+            assert_eq!(products[0].formula, "NH3", "{}", equation);
+            assert_eq!(is_reversible(equation), reversible, "{}", equation);
+            let (.., flag) = parse_reaction(equation).unwrap();
+            assert_eq!(flag, reversible, "{}", equation);
+        }
+        assert!(parse_equation("N2 + H2 <=> NH3 = NH3").is_err());
+    }
+
+    #[test]
+    fn malformed_subscripts() {
+        let message = |f: &str| parse_formula(f).unwrap_err().to_string();
+        assert_eq!(message(""), "Formula is empty");
+        assert_eq!(message("  "), "Formula is empty");
+        assert!(message("H0O").starts_with("Zero subscript at position 1"));
+        assert!(message("Ca(OH)0").starts_with("Zero subscript at position 6"));
+        assert!(message("2").contains("does not follow an element or group"));
+        assert!(message("2(OH)").contains("does not follow an element"));
+        assert!(message("CuSO4·0H2O").contains("hydrate multiplicity"));
+        assert!(matches!(
+            parse_formula("H99999999999"),
+            Err(StoichError::Overflow(_))
+        ));
+        assert_eq!(parse_formula("H0O").unwrap_err().position(), Some(1));
+    }
+
+    #[test]
+    fn huge_counts() {
+        let err = parse_formula("C99999999999").unwrap_err();
+        assert_eq!(err, StoichError::Overflow("99999999999".to_string()));
+        let err = parse_formula("(C99999)99999").unwrap_err();
+        assert_eq!(err.to_string(), "9999800001 does not fit in u32");
+        assert!(parse_formula("C4000000000C4000000000").is_err());
+        assert!(parse_formula("(CH2)4000000000").is_err());
+        assert!(parse_formula("CuSO4·4000000000H2O").is_err());
+    }
+
+    #[test]
+    fn malformed_charge() {
+        assert!(parse_charge("SO4^2").is_err());
+        assert!(parse_charge("SO4^+-").is_err());
+        assert!(parse_charge("SO4^2--").is_err());
+        assert!(parse_charge("Fe^0+").is_err());
+        assert!(parse_charge("^2-").is_err());
+    }
+
+    #[test]
+    fn charged_compound() {
+        let sulfate = Compound::from_formula("SO4^2-").unwrap();
+        assert_eq!(sulfate.charge, -2);
+        let expected: HashMap<&str, u32> =
+            [("S", 1), ("O", 4)].iter().cloned().collect();
+        assert_eq!(sulfate.atoms, e(expected));
+        assert_eq!(Compound::from_formula("H2O").unwrap().charge, 0);
+    }
+
+    #[test]
+    fn equation_with_equals() {
+        let (reagents, products) = parse_equation("H2 + O2 = H2O").unwrap();
+        let formulas: Vec<&str> =
+            reagents.iter().map(|c| c.formula.as_str()).collect();
+        assert_eq!(formulas, vec!["H2", "O2"]);
+        assert_eq!(products.len(), 1);
+        assert_eq!(products[0].formula, "H2O");
+    }
+
+    #[test]
+    fn equation_with_arrows() {
+        for equation in &["Al+Cl2->AlCl3", "Al + Cl2 → AlCl3"] {
+            let (reagents, products) = parse_equation(equation).unwrap();
+            assert_eq!(reagents.len(), 2);
+            assert_eq!(products[0].formula, "AlCl3");
+        }
+    }
+
+    #[test]
+    fn equation_strips_coefficients() {
+        let (reagents, products) =
+            parse_equation("  2 H2 +O2  =   2H2O ").unwrap();
+        assert_eq!(reagents[0].formula, "H2");
+        assert_eq!(reagents[1].formula, "O2");
+        assert_eq!(products[0].formula, "H2O");
+    }
+
+    #[test]
+    fn equation_with_charges() {
+        let (reagents, products) =
+            parse_equation("MnO4^- + Fe2+ + H+ = Mn2+ + Fe3+ + H2O").unwrap();
+        let charges: Vec<i32> = reagents.iter().map(|c| c.charge).collect();
+        assert_eq!(charges, vec![-1, 2, 1]);
+        let charges: Vec<i32> = products.iter().map(|c| c.charge).collect();
+        assert_eq!(charges, vec![2, 3, 0]);
+        let (reagents, _) = parse_equation("Fe^2++H+ = Fe^3+").unwrap();
+        assert_eq!(reagents.len(), 2);
+    }
+
+    #[test]
+    fn equation_missing_arrow() {
+        let result = parse_equation("H2 + O2 H2O");
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No reaction arrow"));
+    }
+
+    #[test]
+    fn equation_empty_side() {
+        let result = parse_equation(" = H2O");
+        assert!(result.unwrap_err().to_string().contains("No reagents"));
+        let result = parse_equation("H2 + O2 ->");
+        assert!(result.unwrap_err().to_string().contains("No products"));
+    }
+
+    #[test]
+    fn equation_empty_term() {
+        let result = parse_equation("H2 + + O2 = H2O");
+        assert!(result.unwrap_err().to_string().contains("Empty term"));
+    }
+
+    #[test]
+    fn equation_invalid_term() {
+        let result = parse_equation("H2 + Xq = H2O");
+        assert!(result.unwrap_err().to_string().contains("\"Xq\""));
+    }
+
+    #[test]
+    fn error_positions() {
+        let result = parse_equation("H2 + O2 = 2 H2O)");
+        assert_eq!(result.unwrap_err().position(), Some(15));
+        let result = parse_formula("CuSO4·5H2O)");
+        assert_eq!(result.unwrap_err().position(), Some(11));
+        let result = parse_formula("Fe^x");
+        assert_eq!(result.unwrap_err().position(), Some(3));
+    }
+
+    #[test]
+    fn unknown_symbol_suggestions() {
+        let err = parse_formula("H2Clo4").unwrap_err();
+        assert_eq!(err.position(), Some(2));
+        assert!(err.to_string().contains("\"Clo\""), "{}", err);
+        assert!(err.to_string().contains("did you mean Cl + O?"), "{}", err);
+        let err = parse_formula("Xx2").unwrap_err();
+        assert!(err.to_string().contains("did you mean Xe?"), "{}", err);
+        let err = parse_formula("NaCL").unwrap_err();
+        assert_eq!(err.position(), Some(3));
+        let err = parse_equation("H2 + O2 = h2O").unwrap_err();
+        assert_eq!(err.position(), Some(10));
+        assert!(err.to_string().contains("did you mean H?"), "{}", err);
+    }
+
+    #[test]
+    fn symbol_casing() {
+        let cobalt: HashMap<&str, u32> = [("Co", 1)].iter().cloned().collect();
+        assert_eq!(parse_formula("Co").unwrap(), e(cobalt));
+        let monoxide: HashMap<&str, u32> =
+            [("C", 1), ("O", 1)].iter().cloned().collect();
+        assert_eq!(parse_formula("CO").unwrap(), e(monoxide));
+        let cobalt_chloride: HashMap<&str, u32> =
+            [("Co", 1), ("Cl", 2)].iter().cloned().collect();
+        assert_eq!(parse_formula("CoCl2").unwrap(), e(cobalt_chloride));
+        let err = parse_formula("co").unwrap_err();
+        assert!(err.to_string().contains("did you mean Co?"), "{}", err);
+        let err = parse_formula("cO").unwrap_err();
+        assert_eq!(err.position(), Some(0));
+        assert_eq!(parse_formula("CO2").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn non_ascii_characters() {
+        for (formula, position) in [("Cé", 1), ("H2Oé", 3), ("éH", 0)] {
+            let err = parse_formula(formula).unwrap_err();
+            assert!(matches!(err, StoichError::ParseError { .. }), "{}", err);
+            assert_eq!(err.position(), Some(position), "{}", err);
+            assert!(
+                err.to_string().contains("Invalid character 'é'"),
+                "{}",
+                err
+            );
+        }
+        assert!(parse_formula("Ωx").is_err());
+        assert!(parse_equation("Cé = Cé").is_err());
+    }
+
+    #[test]
+    fn unicode_subscripts() {
+        let water: HashMap<&str, u32> =
+            [("H", 2), ("O", 1)].iter().cloned().collect();
+        assert_eq!(parse_formula("H₂O").unwrap(), e(water));
+        let rust: HashMap<&str, u32> =
+            [("Fe", 2), ("O", 3)].iter().cloned().collect();
+        assert_eq!(parse_formula("Fe₂O₃").unwrap(), e(rust));
+        let sucrose: HashMap<&str, u32> =
+            [("C", 12), ("H", 22), ("O", 11)].iter().cloned().collect();
+        assert_eq!(parse_formula("C1₂H₂2O₁₁").unwrap(), e(sucrose));
+    }
+
+    #[test]
+    fn isotope_labels() {
+        let carbon = Element::from_symbol("C").unwrap();
+        let hydrogen = Element::from_symbol("H").unwrap();
+        let oxygen = Element::from_symbol("O").unwrap();
+        let labelled = parse_formula("^13CH3CH3").unwrap();
+        assert_eq!(labelled.get(&carbon.isotope(13).unwrap()), Some(&1));
+        assert_eq!(labelled.get(&carbon), Some(&1));
+        assert_eq!(parse_formula("13CH3CH3").unwrap(), labelled);
+        assert_eq!(parse_formula("¹³CH3CH3").unwrap(), labelled);
+        let heavy_water = parse_formula("2H2O").unwrap();
+        assert_eq!(heavy_water.get(&hydrogen.isotope(2).unwrap()), Some(&2));
+        assert_eq!(heavy_water.get(&hydrogen), None);
+        let labelled_water = parse_formula("H2^18O").unwrap();
+        assert_eq!(labelled_water.get(&oxygen.isotope(18).unwrap()), Some(&1));
+        assert_eq!(parse_charge("^13CH3^+").unwrap(), ("^13CH3", 1));
+    }
+
+    #[test]
+    fn invalid_isotope_labels() {
+        assert!(parse_formula("^CH4").is_err());
+        assert!(parse_formula("^13").is_err());
+        assert!(parse_formula("CH3^15C").is_err());
+        assert!(parse_formula("C(13)").is_err());
+    }
+
+    #[test]
+    fn deuterium_and_tritium() {
+        assert_eq!(
+            parse_formula("D2O").unwrap(),
+            parse_formula("2H2O").unwrap()
+        );
+        assert_eq!(
+            parse_formula("T2O").unwrap(),
+            parse_formula("^3H2O").unwrap()
+        );
+        let mixed = parse_formula("CHDT").unwrap();
+        assert_eq!(mixed.len(), 4);
+        let tin: HashMap<&str, u32> = [("Ts", 1)].iter().cloned().collect();
+        assert_eq!(parse_formula("Ts").unwrap(), e(tin));
+    }
+
+    #[test]
+    fn equation_coefficients() {
+        let (rg, pd) =
+            parse_equation_with_coefficients("2 H2 + O2 = 2H2O").unwrap();
+        let coefficients: Vec<Option<u32>> =
+            rg.iter().chain(pd.iter()).map(|(_, c)| *c).collect();
+        assert_eq!(coefficients, vec![Some(2), None, Some(2)]);
+        assert_eq!(pd[0].0.formula, "H2O");
+        let err = parse_equation_with_coefficients("0 H2 + O2 = H2O");
+        assert!(err.unwrap_err().to_string().contains("Invalid coefficient"));
+        assert!(parse_equation("0 H2 + O2 = H2O").is_ok());
+    }
+
+    #[test]
+    fn abbreviations() {
+        assert_eq!(expand_abbreviations("MeOH"), "(CH3)OH");
+        assert_eq!(expand_abbreviations("PhCOOH"), "(C6H5)COOH");
+        assert_eq!(expand_abbreviations("Et2O"), "(C2H5)2O");
+        assert_eq!(expand_abbreviations("tBuOH"), "(C4H9)OH");
+        assert_eq!(expand_abbreviations("SnBu4"), "Sn(C4H9)4");
+        assert_eq!(expand_abbreviations("MnO4^-"), "MnO4^-");
+        assert_eq!(expand_abbreviations("PrCl3"), "PrCl3");
+        let methanol: HashMap<&str, u32> =
+            [("C", 1), ("H", 4), ("O", 1)].iter().cloned().collect();
+        assert_eq!(
+            parse_formula(&expand_abbreviations("MeOH")).unwrap(),
+            e(methanol)
+        );
+    }
+
+    #[test]
+    fn equation_as_formula() {
+        let err = parse_formula("H2+O2=H2O").unwrap_err();
+        assert!(err.to_string().contains("parse_equation"), "{}", err);
+        assert_eq!(err.position(), Some(2));
+        let err = Compound::from_formula("CH4 -> CO2").unwrap_err();
+        assert!(err.to_string().contains("parse_equation"), "{}", err);
+        assert!(parse_formula("NH4+").is_ok());
+        assert!(parse_formula("SO4^2-").is_ok());
+    }
+
+    #[test]
+    fn test_repeat_unit() {
+        let unit = repeat_unit("(C2H4)n").unwrap();
+        assert_eq!(unit.formula, "C2H4");
+        let expected: HashMap<&str, u32> =
+            [("C", 2), ("H", 4)].iter().cloned().collect();
+        assert_eq!(unit.atoms, e(expected));
+        let unit = repeat_unit("[CF2CF2]n").unwrap();
+        let expected: HashMap<&str, u32> =
+            [("C", 2), ("F", 4)].iter().cloned().collect();
+        assert_eq!(unit.atoms, e(expected));
+        let unit = repeat_unit("(CH2)(CO)n").unwrap();
+        assert_eq!(unit.formula, "(CH2)(CO)");
+        assert!(repeat_unit("C2H4").is_err());
+        assert_eq!(repeat_unit("(C2Xx4)n").unwrap_err().position(), Some(3));
+        let err = parse_formula("(C2H4)n").unwrap_err();
+        assert_eq!(err.position(), Some(6));
+        assert!(err.to_string().contains("repeat_unit"));
+    }
 }
@@ -0,0 +1,78 @@
+use crate::error::StoichError;
+
+/// Ion product of water at 25 °C.
+pub const KW: f64 = 1e-14;
+
+fn check_molarity(molarity: f64) -> Result<(), StoichError> {
+    match molarity >= 0.0 {
+        true => Ok(()),
+        false => Err(StoichError::InvalidInput(format!(
+            "Molarity must not be negative, got {}",
+            molarity
+        ))),
+    }
+}
+
+// Concentration of H+ (or OH-) when `added` mol/L is supplied on top of
+// water's own autoionization, so very dilute solutions tend to pH 7.
+fn with_water(added: f64) -> f64 {
+    (added + (added * added + 4.0 * KW).sqrt()) / 2.0
+}
+
+/// pH of a monoprotic strong acid at `molarity`.
+pub fn ph_strong_acid(molarity: f64) -> Result<f64, StoichError> {
+    check_molarity(molarity)?;
+    Ok(-with_water(molarity).log10())
+}
+
+/// pH of a strong base releasing one hydroxide per formula unit.
+pub fn ph_strong_base(molarity: f64) -> Result<f64, StoichError> {
+    check_molarity(molarity)?;
+    Ok(14.0 + with_water(molarity).log10())
+}
+
+/// pH of a monoprotic weak acid with dissociation constant `ka`, solving
+/// the equilibrium quadratic rather than assuming little dissociation.
+pub fn ph_weak_acid(molarity: f64, ka: f64) -> Result<f64, StoichError> {
+    check_molarity(molarity)?;
+    if ka.is_nan() || ka <= 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Ka must be positive, got {}",
+            ka
+        )));
+    }
+    // x^2 + Ka x - Ka C = 0
+    let dissociated = (-ka + (ka * ka + 4.0 * ka * molarity).sqrt()) / 2.0;
+    Ok(-with_water(dissociated).log10())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ph::{ph_strong_acid, ph_strong_base, ph_weak_acid};
+
+    #[test]
+    fn strong_acid_and_base() {
+        assert!((ph_strong_acid(0.1).unwrap() - 1.0).abs() < 1e-6);
+        assert!((ph_strong_base(0.1).unwrap() - 13.0).abs() < 1e-6);
+        assert!((ph_strong_acid(0.0).unwrap() - 7.0).abs() < 1e-9);
+        assert!(ph_strong_acid(-1.0).is_err());
+    }
+
+    #[test]
+    fn very_dilute() {
+        let ph = ph_strong_acid(1e-8).unwrap();
+        assert!(ph < 7.0 && ph > 6.9, "{}", ph);
+        let ph = ph_strong_base(1e-8).unwrap();
+        assert!(ph > 7.0 && ph < 7.1, "{}", ph);
+    }
+
+    #[test]
+    fn weak_acid() {
+        let acetic = ph_weak_acid(0.1, 1.8e-5).unwrap();
+        assert!((acetic - 2.87).abs() < 0.01, "{}", acetic);
+        // The approximation sqrt(Ka C) would give pH 1.48 here.
+        let chlorous = ph_weak_acid(0.1, 1.1e-2).unwrap();
+        assert!((chlorous - 1.55).abs() < 0.01, "{}", chlorous);
+        assert!(ph_weak_acid(0.1, 0.0).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+pub use crate::error::{Result, StoichError};
+pub use crate::model::{BalancedReaction, Compound, Reactant, Substance};
+pub use crate::parse::{parse_equation, parse_reaction};
+#[cfg(feature = "balance")]
+pub use crate::solve::{balance, balance_equation};
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn balance_with_prelude() {
+        let reaction = balance_equation("H2 + O2 = H2O").unwrap();
+        assert_eq!(reaction.to_string(), "2 H2 + O2 -> 2 H2O");
+        assert!(matches!(
+            balance_equation("H2 = O2"),
+            Err(StoichError::UnbalanceableElements { .. })
+        ));
+        let (reagents, products) = parse_equation("N2 + H2 <=> NH3").unwrap();
+        let reaction: BalancedReaction = balance(reagents, products).unwrap();
+        assert_eq!(reaction.to_string(), "N2 + 3 H2 -> 2 NH3");
+        let reaction = balance_equation("N2 + H2 <=> NH3").unwrap();
+        assert_eq!(reaction.to_string(), "N2 + 3 H2 <=> 2 NH3");
+    }
+
+    #[test]
+    fn substance_with_prelude() {
+        let water: Result<Substance> = Substance::from_formula("H2O", 18.0, 1);
+        assert!(water.is_ok());
+        assert!(Compound::from_formula("Xx").is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::StoichError;
+
+fn check_figures(figures: u32) -> Result<(), StoichError> {
+    match figures > 0 {
+        true => Ok(()),
+        false => Err(StoichError::InvalidInput(
+            "Need at least one significant figure".to_string(),
+        )),
+    }
+}
+
+// Exponent formatting rounds the value as stored, avoiding the error that
+// scaling by a power of ten and rounding would add.
+fn scientific(value: f64, figures: u32) -> String {
+    format!("{:.*e}", figures as usize - 1, value)
+}
+
+/// Round `value` to `figures` significant figures, e.g. 0.012345 to three
+/// figures is 0.0123 and 98765 to two is 99000.
+pub fn round_sig_figs(value: f64, figures: u32) -> Result<f64, StoichError> {
+    check_figures(figures)?;
+    if !value.is_finite() {
+        return Ok(value);
+    }
+    Ok(scientific(value, figures).parse().unwrap_or(value))
+}
+
+/// Like `round_sig_figs`, but written out with the trailing zeros that
+/// carry significance, so 2.5 to three figures is `2.50`.
+pub fn format_sig_figs(
+    value: f64,
+    figures: u32,
+) -> Result<String, StoichError> {
+    check_figures(figures)?;
+    if value == 0.0 || !value.is_finite() {
+        return Ok(format!("{:.*}", figures as usize - 1, value));
+    }
+    let rounded = scientific(value, figures);
+    let exponent: i32 = rounded
+        .split('e')
+        .nth(1)
+        .and_then(|e| e.parse().ok())
+        .unwrap_or(0);
+    let decimals = (figures as i32 - 1 - exponent).max(0) as usize;
+    Ok(format!(
+        "{:.*}",
+        decimals,
+        rounded.parse::<f64>().unwrap_or(value)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sigfig::{format_sig_figs, round_sig_figs};
+
+    #[test]
+    fn rounding() {
+        assert_eq!(round_sig_figs(0.012345, 3).unwrap(), 0.0123);
+        assert_eq!(round_sig_figs(98765.0, 2).unwrap(), 99000.0);
+        assert_eq!(round_sig_figs(-2.346, 3).unwrap(), -2.35);
+        assert_eq!(round_sig_figs(18.015, 4).unwrap(), 18.02);
+        assert!(round_sig_figs(1.0, 0).is_err());
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_sig_figs(2.5, 3).unwrap(), "2.50");
+        assert_eq!(format_sig_figs(0.000123456, 2).unwrap(), "0.00012");
+        assert_eq!(format_sig_figs(98765.0, 2).unwrap(), "99000");
+        assert_eq!(format_sig_figs(9.996, 3).unwrap(), "10.0");
+        assert_eq!(format_sig_figs(0.0, 3).unwrap(), "0.00");
+        assert!(format_sig_figs(1.0, 0).is_err());
+    }
+}
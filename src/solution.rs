@@ -0,0 +1,184 @@
+use crate::error::StoichError;
+use crate::model::{Compound, Substance};
+
+fn check_volume(volume_l: f64) -> Result<(), StoichError> {
+    match volume_l > 0.0 {
+        true => Ok(()),
+        false => Err(StoichError::InvalidInput(format!(
+            "Volume must be positive, got {} L",
+            volume_l
+        ))),
+    }
+}
+
+/// Moles of `substance` per liter when dissolved to `volume_l` liters.
+pub fn molarity(
+    substance: &Substance,
+    volume_l: f64,
+) -> Result<f64, StoichError> {
+    check_volume(volume_l)?;
+    Ok(substance.moles() / volume_l)
+}
+
+/// Grams of `formula` needed to make `volume_l` liters at `molarity`.
+pub fn mass_for_molarity(
+    formula: &str,
+    molarity: f64,
+    volume_l: f64,
+) -> Result<f64, StoichError> {
+    check_volume(volume_l)?;
+    if molarity < 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Molarity must not be negative, got {}",
+            molarity
+        )));
+    }
+    let compound = Compound::from_formula(formula)?;
+    Ok(molarity * volume_l * compound.molar_mass())
+}
+
+/// Final volume after diluting `v1` of concentration `c1` down to `c2`,
+/// from C1V1 = C2V2.
+pub fn dilute(c1: f64, v1: f64, c2: f64) -> Result<f64, StoichError> {
+    dilution(Some(c1), Some(v1), Some(c2), None)
+}
+
+/// Solve C1V1 = C2V2 for whichever of the four quantities is `None`.
+pub fn dilution(
+    c1: Option<f64>,
+    v1: Option<f64>,
+    c2: Option<f64>,
+    v2: Option<f64>,
+) -> Result<f64, StoichError> {
+    let (product, divisor) = match (c1, v1, c2, v2) {
+        (None, Some(v1), Some(c2), Some(v2)) => (c2 * v2, v1),
+        (Some(c1), None, Some(c2), Some(v2)) => (c2 * v2, c1),
+        (Some(c1), Some(v1), None, Some(v2)) => (c1 * v1, v2),
+        (Some(c1), Some(v1), Some(c2), None) => (c1 * v1, c2),
+        _ => {
+            return Err(StoichError::InvalidInput(
+                "Exactly one of c1, v1, c2 and v2 must be unknown".to_string(),
+            ))
+        }
+    };
+    if divisor == 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Cannot solve dilution {} = 0 x ?",
+            product
+        )));
+    }
+    Ok(product / divisor)
+}
+
+fn check_solution_mass(solution_mass_g: f64) -> Result<(), StoichError> {
+    match solution_mass_g > 0.0 {
+        true => Ok(()),
+        false => Err(StoichError::InvalidInput(format!(
+            "Solution mass must be positive, got {} g",
+            solution_mass_g
+        ))),
+    }
+}
+
+/// Parts per million by mass, i.e. mg of solute per kg of solution.
+pub fn ppm(
+    solute_mass_g: f64,
+    solution_mass_g: f64,
+) -> Result<f64, StoichError> {
+    check_solution_mass(solution_mass_g)?;
+    Ok(solute_mass_g / solution_mass_g * 1e6)
+}
+
+/// Parts per billion by mass, i.e. µg of solute per kg of solution.
+pub fn ppb(
+    solute_mass_g: f64,
+    solution_mass_g: f64,
+) -> Result<f64, StoichError> {
+    check_solution_mass(solution_mass_g)?;
+    Ok(solute_mass_g / solution_mass_g * 1e9)
+}
+
+/// Grams of solute giving `ppm` parts per million in `solution_mass_g`.
+pub fn mass_for_ppm(
+    ppm: f64,
+    solution_mass_g: f64,
+) -> Result<f64, StoichError> {
+    check_solution_mass(solution_mass_g)?;
+    Ok(ppm * solution_mass_g / 1e6)
+}
+
+/// Grams of solute giving `ppb` parts per billion in `solution_mass_g`.
+pub fn mass_for_ppb(
+    ppb: f64,
+    solution_mass_g: f64,
+) -> Result<f64, StoichError> {
+    check_solution_mass(solution_mass_g)?;
+    Ok(ppb * solution_mass_g / 1e9)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Substance;
+    use crate::solution::{
+        dilute, dilution, mass_for_molarity, mass_for_ppb, mass_for_ppm,
+        molarity, ppb, ppm,
+    };
+
+    #[test]
+    fn sodium_chloride_molarity() {
+        let salt = Substance::from_formula("NaCl", 58.44, 1).unwrap();
+        let m = molarity(&salt, 0.5).unwrap();
+        assert!((m - 2.0).abs() < 1e-3, "{}", m);
+    }
+
+    #[test]
+    fn mass_for_glucose_solution() {
+        let grams = mass_for_molarity("C6H12O6", 0.1, 0.25).unwrap();
+        assert!((grams - 4.504).abs() < 1e-2, "{}", grams);
+    }
+
+    #[test]
+    fn invalid_volume() {
+        let salt = Substance::from_formula("NaCl", 58.44, 1).unwrap();
+        assert!(molarity(&salt, 0.0).is_err());
+        assert!(molarity(&salt, -1.0).is_err());
+        assert!(mass_for_molarity("NaCl", 1.0, 0.0).is_err());
+        assert!(mass_for_molarity("NaCl", -1.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn tenfold_dilution() {
+        assert_eq!(dilute(1.0, 0.1, 0.1).unwrap(), 1.0);
+        assert_eq!(
+            dilution(None, Some(0.1), Some(0.1), Some(1.0)).unwrap(),
+            1.0
+        );
+        assert_eq!(
+            dilution(Some(1.0), None, Some(0.1), Some(1.0)).unwrap(),
+            0.1
+        );
+        assert_eq!(
+            dilution(Some(1.0), Some(0.1), None, Some(1.0)).unwrap(),
+            0.1
+        );
+    }
+
+    #[test]
+    fn invalid_dilution() {
+        assert!(dilute(1.0, 0.1, 0.0).is_err());
+        assert!(dilution(None, None, Some(0.1), Some(1.0)).is_err());
+        assert!(dilution(Some(1.0), Some(0.1), Some(0.1), Some(1.0)).is_err());
+    }
+
+    #[test]
+    fn parts_per_million() {
+        // 1 mg in 1 kg
+        assert!((ppm(0.001, 1000.0).unwrap() - 1.0).abs() < 1e-9);
+        assert!((ppb(0.001, 1000.0).unwrap() - 1000.0).abs() < 1e-6);
+        assert!((mass_for_ppm(1.0, 1000.0).unwrap() - 0.001).abs() < 1e-12);
+        assert!((mass_for_ppb(1000.0, 1000.0).unwrap() - 0.001).abs() < 1e-12);
+        assert!(ppm(1.0, 0.0).is_err());
+        assert!(ppb(1.0, 0.0).is_err());
+        assert!(mass_for_ppm(1.0, -5.0).is_err());
+    }
+}
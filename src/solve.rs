@@ -1,154 +1,1249 @@
-use std::cmp::max;
-use std::collections::{HashMap, HashSet};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::panic;
+use std::path::Path;
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{self, AtomicUsize};
+#[cfg(feature = "parallel")]
+use std::thread;
 
-use itertools::Itertools;
-use nalgebra::DMatrix;
-use num::integer::lcm;
-use rug::Rational;
+use rug::{Integer, Rational};
 
-use crate::model::{BalancedReaction, Compound, Element, Reactant};
+use crate::error::StoichError;
+use crate::model::{BalancedReaction, Compound, Element, Reactant, State};
+use crate::molecule::{formula_string, oxidation_states};
+use crate::parse::{parse_formula, parse_reaction};
+
+const DIATOMIC: [&str; 7] = ["H", "N", "O", "F", "Cl", "Br", "I"];
+
+const STRONG_ACIDS: [&str; 7] =
+    ["HCl", "HBr", "HI", "HNO3", "H2SO4", "HClO4", "HClO3"];
+
+// Fully deprotonated anions come before their hydrogen forms so that strong
+// acids dissociate completely.
+const ANIONS: [&str; 27] = [
+    "PO4^3-", "HPO4^2-", "H2PO4-", "SO4^2-", "HSO4-", "SO3^2-", "CO3^2-",
+    "HCO3-", "Cr2O7^2-", "CrO4^2-", "C2O4^2-", "MnO4-", "ClO4-", "ClO3-",
+    "ClO2-", "ClO-", "NO3-", "NO2-", "C2H3O2-", "SCN-", "CN-", "OH-", "F-",
+    "Cl-", "Br-", "I-", "S^2-",
+];
+
+/// Balanced equations, each with the line it was read from.
+pub type LineResults = Vec<(usize, Result<BalancedReaction, StoichError>)>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Medium {
+    Acidic,
+    Basic,
+}
+
+/// Settings for `balance_with_options`. The default matches `balance`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct BalanceOptions {
+    /// Reject solutions with any coefficient above this. A low cap guards
+    /// against runaway answers to mistyped equations, but also rejects
+    /// genuine reactions with large coefficients. `None` allows any `u32`.
+    pub max_coefficient: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum BalanceResult {
+    Balanced(BalancedReaction),
+    /// The equation has several independent solutions, given as a basis of
+    /// coefficient vectors over reagents followed by products.
+    Underdetermined(Vec<Vec<Rational>>),
+}
+
+impl BalanceResult {
+    fn into_reaction(self) -> Result<BalancedReaction, StoichError> {
+        match self {
+            BalanceResult::Balanced(reaction) => Ok(reaction),
+            BalanceResult::Underdetermined(basis) => {
+                Err(StoichError::Underdetermined(basis))
+            }
+        }
+    }
+}
 
 pub fn balance(
     reagents: Vec<Compound>,
     products: Vec<Compound>,
-) -> Result<BalancedReaction, String> {
+) -> Result<BalancedReaction, StoichError> {
+    balance_result(reagents, products)?.into_reaction()
+}
+
+/// Parse and balance `equation`, keeping an equilibrium arrow: `A <=> B`
+/// balances to a reversible reaction.
+pub fn balance_equation(
+    equation: &str,
+) -> Result<BalancedReaction, StoichError> {
+    let (reagents, products, reversible) = parse_reaction(equation)?;
+    let mut reaction = balance(reagents, products)?;
+    reaction.reversible = reversible;
+    Ok(reaction)
+}
+
+/// The intermediate results of `balance_verbose`, for showing how an
+/// equation was balanced.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BalanceSteps {
+    /// Element symbols, one per matrix row, then `charge` for ionic
+    /// equations.
+    pub elements: Vec<String>,
+    /// Reagent then product formulas, one per matrix column.
+    pub compounds: Vec<String>,
+    /// Atom counts, with the product columns negated.
+    pub matrix: Vec<Vec<Rational>>,
+    /// `matrix` in reduced row echelon form.
+    pub reduced: Vec<Vec<Rational>>,
+    pub null_space: Vec<Rational>,
+    /// The factor taking `null_space` to the balanced coefficients.
+    pub scale: Rational,
+}
+
+impl fmt::Display for BalanceSteps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = |f: &mut fmt::Formatter<'_>,
+                     rows: &[Vec<Rational>]|
+         -> fmt::Result {
+            writeln!(f, "\t{}", self.compounds.join("\t"))?;
+            for (element, row) in self.elements.iter().zip(rows) {
+                let row: Vec<String> =
+                    row.iter().map(|v| v.to_string()).collect();
+                writeln!(f, "{}\t{}", element, row.join("\t"))?;
+            }
+            Ok(())
+        };
+        writeln!(f, "Matrix:")?;
+        table(f, &self.matrix)?;
+        writeln!(f, "Reduced:")?;
+        table(f, &self.reduced)?;
+        let vector: Vec<String> =
+            self.null_space.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "Null space: [{}]", vector.join(", "))?;
+        write!(f, "Scale: {}", self.scale)
+    }
+}
+
+/// Like `balance`, but also returns the matrix, its reduced form, the
+/// null-space vector and the scaling factor used to reach the result.
+pub fn balance_verbose(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+) -> Result<(BalancedReaction, BalanceSteps), StoichError> {
+    let reaction = balance(reagents.clone(), products.clone())?;
+    let elements = shared_elements(&reagents, &products)?;
+    let compounds: Vec<&Compound> =
+        reagents.iter().chain(products.iter()).collect();
+    let charged = has_charge(&compounds);
+    let matrix = build_matrix(&elements, &compounds, reagents.len(), charged);
+    let (reduced, _) = row_reduce(matrix.clone(), compounds.len());
+    let vector = null_space(matrix.clone(), compounds.len()).remove(0);
+    let (index, value) = vector
+        .iter()
+        .enumerate()
+        .find(|(_, v)| v.cmp0() != Ordering::Equal)
+        .expect("a balanced equation has a nonzero solution");
+    let coefficient = reaction
+        .reactants
+        .iter()
+        .chain(reaction.products.iter())
+        .nth(index)
+        .map(|r| r.molar_coefficient)
+        .unwrap_or(0);
+    let scale = Rational::from(coefficient) / value;
+    let steps = BalanceSteps {
+        elements: elements
+            .iter()
+            .map(|e| e.notation())
+            .chain(charged.then(|| "charge".to_string()))
+            .collect(),
+        compounds: compounds.iter().map(|c| c.formula.clone()).collect(),
+        matrix,
+        reduced,
+        null_space: vector,
+        scale,
+    };
+    Ok((reaction, steps))
+}
+
+/// Parse and balance each equation, keeping the results in input order. A
+/// failure, even a panic inside the solver, only affects its own entry.
+/// With the `parallel` feature the equations are shared out over threads.
+pub fn balance_batch(
+    equations: &[&str],
+) -> Vec<Result<BalancedReaction, StoichError>> {
+    #[cfg(feature = "parallel")]
+    return balance_parallel(equations);
+    #[cfg(not(feature = "parallel"))]
+    return balance_each(equations);
+}
+
+fn balance_one(equation: &str) -> Result<BalancedReaction, StoichError> {
+    panic::catch_unwind(|| balance_equation(equation)).unwrap_or_else(|_| {
+        Err(StoichError::Unbalanceable(format!(
+            "Balancing {:?} failed unexpectedly",
+            equation
+        )))
+    })
+}
+
+#[cfg(any(test, not(feature = "parallel")))]
+fn balance_each(
+    equations: &[&str],
+) -> Vec<Result<BalancedReaction, StoichError>> {
+    equations
+        .iter()
+        .map(|equation| balance_one(equation))
+        .collect()
+}
+
+// Each thread claims the next unbalanced equation until none are left, so
+// a slow equation only holds up its own thread. Results are put back in
+// input order.
+#[cfg(feature = "parallel")]
+fn balance_parallel(
+    equations: &[&str],
+) -> Vec<Result<BalancedReaction, StoichError>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(equations.len());
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<BalancedReaction, StoichError>)> =
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut done = vec![];
+                        loop {
+                            let i =
+                                next.fetch_add(1, atomic::Ordering::Relaxed);
+                            match equations.get(i) {
+                                Some(equation) => {
+                                    done.push((i, balance_one(equation)))
+                                }
+                                None => return done,
+                            }
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .flat_map(|h| h.join().expect("balance_one catches panics"))
+                .collect()
+        });
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Balance one equation per line of `reader`, skipping blank lines and
+/// lines starting with `#`. Each result is paired with its line number,
+/// counting from 1.
+pub fn balance_from_reader<R: BufRead>(
+    reader: R,
+) -> Result<LineResults, StoichError> {
+    let mut numbers = vec![];
+    let mut lines = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| {
+            StoichError::InvalidInput(format!(
+                "Could not read line {}: {}",
+                i + 1,
+                e
+            ))
+        })?;
+        let equation = line.trim();
+        if equation.is_empty() || equation.starts_with('#') {
+            continue;
+        }
+        numbers.push(i + 1);
+        lines.push(equation.to_string());
+    }
+    let equations: Vec<&str> = lines.iter().map(String::as_str).collect();
+    Ok(numbers.into_iter().zip(balance_batch(&equations)).collect())
+}
+
+/// `balance_from_reader` over the file at `path`.
+pub fn balance_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<LineResults, StoichError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| {
+        StoichError::InvalidInput(format!(
+            "Could not read file {:?}: {}",
+            path, e
+        ))
+    })?;
+    balance_from_reader(BufReader::new(file))
+}
+
+/// Like `balance`, but returns a solution basis instead of an error when
+/// the equation has no unique balanced form.
+pub fn balance_result(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+) -> Result<BalanceResult, StoichError> {
+    solve_reaction(reagents, products)
+}
+
+/// The exact coefficients of a balanced equation, over reagents followed by
+/// products, scaled so the first nonzero coefficient is 1. Unlike `balance`
+/// these are not made integers or checked for sign, so a negative value
+/// means that species belongs on the other side.
+pub fn balance_rational(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+) -> Result<Vec<Rational>, StoichError> {
+    let mut basis = solution_basis(&reagents, &products)?;
+    if basis.len() > 1 {
+        return Err(StoichError::Underdetermined(basis));
+    }
+    let solution = basis.remove(0);
+    let first = solution
+        .iter()
+        .find(|r| r.cmp0() != Ordering::Equal)
+        .cloned()
+        .ok_or_else(|| {
+            StoichError::Unbalanceable(
+                "Equation could not be balanced!".to_string(),
+            )
+        })?;
+    Ok(solution.into_iter().map(|r| r / &first).collect())
+}
+
+/// Balance an equation in which some coefficients are already given, as
+/// returned by `parse_equation_with_coefficients`. Given coefficients are
+/// kept as written and the rest are solved for; without any, this is the
+/// same as `balance`.
+pub fn balance_fixed(
+    reagents: Vec<(Compound, Option<u32>)>,
+    products: Vec<(Compound, Option<u32>)>,
+) -> Result<BalancedReaction, StoichError> {
+    let split = reagents.len();
+    let (compounds, fixed): (Vec<Compound>, Vec<Option<u32>>) =
+        reagents.into_iter().chain(products).unzip();
+    let given: Vec<(usize, u32)> = fixed
+        .iter()
+        .enumerate()
+        .filter_map(|(i, coeff)| coeff.map(|c| (i, c)))
+        .collect();
+    let (reagents, products) = compounds.split_at(split);
+    let (anchor, anchor_coeff) = match given.first() {
+        Some(first) => *first,
+        None => return balance(reagents.to_vec(), products.to_vec()),
+    };
+    let elements = shared_elements(reagents, products)?;
+    let columns: Vec<&Compound> = compounds.iter().collect();
+    let mut matrix =
+        build_matrix(&elements, &columns, split, has_charge(&columns));
+    // Keep every given coefficient in proportion to the first one.
+    for (i, coeff) in &given[1..] {
+        let mut row = vec![Rational::new(); compounds.len()];
+        row[anchor] = Rational::from(*coeff);
+        row[*i] = -Rational::from(anchor_coeff);
+        matrix.push(row);
+    }
+    let basis = null_space(matrix, compounds.len());
+    debug!("Null space basis with fixed coefficients: {:?}", basis);
+    let inconsistent = || {
+        StoichError::Unbalanceable(
+            "Given coefficients are inconsistent with conservation of atoms"
+                .to_string(),
+        )
+    };
+    let solution = match basis.as_slice() {
+        [solution] if solution[anchor] != 0 => solution,
+        [_] | [] => return Err(inconsistent()),
+        _ => return Err(StoichError::Underdetermined(basis)),
+    };
+    let scale = Rational::from(anchor_coeff) / &solution[anchor];
+    let coefficients = solution
+        .iter()
+        .map(|value| {
+            let value = Rational::from(value * &scale);
+            match value.cmp0() == Ordering::Greater && *value.denom() == 1 {
+                true => to_coefficient(value.numer().clone()),
+                false => Err(StoichError::Unbalanceable(format!(
+                    "Given coefficients would need a coefficient of {}",
+                    value
+                ))),
+            }
+        })
+        .collect::<Result<Vec<u32>, StoichError>>()?;
+    let result: Vec<Reactant> = compounds
+        .into_iter()
+        .zip(coefficients)
+        .map(|(c, coeff)| Reactant::of_compound(c, coeff))
+        .collect();
+    let (reagents, products) = result.split_at(split);
+    Ok(BalancedReaction::new(reagents.to_vec(), products.to_vec()))
+}
+
+/// `balance`, tuned by `options`.
+pub fn balance_with_options(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+    options: &BalanceOptions,
+) -> Result<BalancedReaction, StoichError> {
+    let reaction = solve_reaction(reagents, products)?.into_reaction()?;
+    if let Some(max) = options.max_coefficient {
+        let over = reaction
+            .reactants
+            .iter()
+            .chain(reaction.products.iter())
+            .find(|r| r.molar_coefficient > max);
+        if let Some(r) = over {
+            return Err(StoichError::Unbalanceable(format!(
+                "Coefficient {} of {} is over the limit of {}",
+                r.molar_coefficient, r.compound.formula, max
+            )));
+        }
+    }
+    Ok(reaction)
+}
+
+fn solve_reaction(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+) -> Result<BalanceResult, StoichError> {
+    let basis = solution_basis(&reagents, &products)?;
+    let solution = match basis.as_slice() {
+        [solution] => solution,
+        _ => return Ok(BalanceResult::Underdetermined(basis)),
+    };
+    let compounds: Vec<&Compound> =
+        reagents.iter().chain(products.iter()).collect();
+    let coefficients = scale_to_integers(solution)?;
+    debug!("Got integer coefficients: {:?}", coefficients);
+    let unused: Vec<&str> = compounds
+        .iter()
+        .zip(&coefficients)
+        .filter(|(_, coeff)| **coeff == 0)
+        .map(|(c, _)| c.formula.as_str())
+        .collect();
+    if !unused.is_empty() {
+        return Err(StoichError::Unbalanceable(format!(
+            "Equation only balances with a zero coefficient for {}",
+            unused.join(", ")
+        )));
+    }
+    let result: Vec<Reactant> = compounds
+        .into_iter()
+        .cloned()
+        .zip(coefficients)
+        .map(|(c, coeff)| Reactant::of_compound(c, coeff))
+        .collect();
+    let (reagents_result, products_result) = result.split_at(reagents.len());
+    check_balance(reagents_result, products_result)?;
+    let mut reaction = BalancedReaction::new(
+        reagents_result.to_vec(),
+        products_result.to_vec(),
+    );
+    reduce_coefficients(&mut reaction);
+    Ok(BalanceResult::Balanced(reaction))
+}
+
+// The null space of an equation's matrix, which is empty when the equation
+// cannot be balanced.
+fn solution_basis(
+    reagents: &[Compound],
+    products: &[Compound],
+) -> Result<Vec<Vec<Rational>>, StoichError> {
+    let elements = shared_elements(reagents, products)?;
+    let compounds: Vec<&Compound> =
+        reagents.iter().chain(products.iter()).collect();
+    let conserve_charge = has_charge(&compounds);
+    let matrix =
+        build_matrix(&elements, &compounds, reagents.len(), conserve_charge);
+    debug!("Solving equation system");
+    let basis = null_space(matrix, compounds.len());
+    debug!("Null space basis: {:?}", basis);
+    if basis.len() > 1 {
+        // A species on both sides always adds a solution of its own.
+        let shared: Vec<&str> = reagents
+            .iter()
+            .filter(|r| products.contains(r))
+            .map(|r| r.formula.as_str())
+            .collect();
+        if !shared.is_empty() {
+            return Err(StoichError::Unbalanceable(format!(
+                "{} on both sides of the equation, cancel before balancing",
+                shared.join(", ")
+            )));
+        }
+    }
+    if basis.is_empty() {
+        return Err(StoichError::Unbalanceable(
+            match conserve_charge {
+                true => "Equation could not be balanced with charge conserved!",
+                false => "Equation could not be balanced!",
+            }
+            .to_string(),
+        ));
+    }
+    Ok(basis)
+}
+
+// The elements of an equation, which must appear on both sides.
+fn shared_elements<'a>(
+    reagents: &'a [Compound],
+    products: &'a [Compound],
+) -> Result<Vec<&'a Element>, StoichError> {
     let mut reagent_atoms: HashSet<&Element> = HashSet::new();
     let mut product_atoms: HashSet<&Element> = HashSet::new();
-    for r in &reagents {
+    for r in reagents {
         for e in r.atoms.keys() {
             reagent_atoms.insert(e);
         }
     }
-    for p in &products {
+    for p in products {
         for e in p.atoms.keys() {
             product_atoms.insert(e);
         }
     }
     if !&reagent_atoms.eq(&product_atoms) {
-        let missing_products: HashSet<_> =
-            reagent_atoms.difference(&product_atoms).collect();
-        let missing_reagents: HashSet<_> =
-            product_atoms.difference(&reagent_atoms).collect();
-        return Err(format!(
-            "Equation cannot be balanced. Reagent elements that are not in products = {:?}. Product elements that are not in products = {:?}",
-            missing_products, missing_reagents)
-        );
+        let symbols = |missing: HashSet<&&Element>| -> Vec<String> {
+            let mut missing: Vec<&&Element> = missing.into_iter().collect();
+            missing.sort();
+            missing.iter().map(|e| e.notation()).collect()
+        };
+        return Err(StoichError::UnbalanceableElements {
+            missing_products: symbols(
+                reagent_atoms.difference(&product_atoms).collect(),
+            ),
+            missing_reagents: symbols(
+                product_atoms.difference(&reagent_atoms).collect(),
+            ),
+        });
     }
-    let all_atoms: Vec<&Element> = reagent_atoms
+    // Matrix rows follow element order so that results do not depend on
+    // hash iteration order.
+    let mut elements: Vec<&Element> = reagent_atoms.into_iter().collect();
+    elements.sort();
+    Ok(elements)
+}
+
+/// Divide every coefficient by their common factor, so that `4 H2 + 2 O2
+/// = 4 H2O` becomes `2 H2 + O2 = 2 H2O`.
+pub fn reduce_coefficients(reaction: &mut BalancedReaction) {
+    let divisor = reaction
+        .reactants
         .iter()
-        .chain(product_atoms.iter())
+        .chain(reaction.products.iter())
+        .fold(Integer::new(), |acc, r| {
+            acc.gcd(&Integer::from(r.molar_coefficient))
+        });
+    trace!("Reducing reaction coefficients by {}", divisor);
+    if divisor <= 1 {
+        return;
+    }
+    let divisor = divisor.to_u32().unwrap_or(1);
+    for r in reaction
+        .reactants
+        .iter_mut()
+        .chain(reaction.products.iter_mut())
+    {
+        r.molar_coefficient /= divisor;
+    }
+}
+
+/// Balance the complete combustion of `fuel` in oxygen, routing carbon to
+/// CO2, hydrogen to H2O, sulfur to SO2 and nitrogen to N2.
+pub fn balance_combustion(
+    fuel: Compound,
+) -> Result<BalancedReaction, StoichError> {
+    let routes = [("C", "CO2"), ("H", "H2O"), ("S", "SO2"), ("N", "N2")];
+    if let Some(element) = fuel.atoms.keys().find(|e| {
+        e.get_symbol() != "O"
+            && !routes.iter().any(|(s, _)| *s == e.get_symbol())
+    }) {
+        return Err(StoichError::InvalidInput(format!(
+            "Cannot route {} in combustion of {}",
+            element.get_symbol(),
+            fuel.formula
+        )));
+    }
+    let products = routes
+        .iter()
+        .filter(|(symbol, _)| {
+            fuel.atoms.keys().any(|e| e.get_symbol() == *symbol)
+        })
+        .map(|(_, product)| Compound::from_formula(product))
+        .collect::<Result<Vec<Compound>, StoichError>>()?;
+    if products.is_empty() {
+        return Err(StoichError::InvalidInput(format!(
+            "{} has nothing to burn",
+            fuel.formula
+        )));
+    }
+    debug!("Combustion products of {}: {:?}", fuel.formula, products);
+    balance(vec![fuel, Compound::from_formula("O2")?], products)
+}
+
+/// Find the one compound missing from an equation such as
+/// `CaCO3 = CaO + ?` and balance it. The missing compound holds every
+/// element found on only one side, plus as few shared elements as give a
+/// unique solution; several equally simple candidates are an error.
+pub fn infer_missing(
+    known_reagents: Vec<Compound>,
+    known_products: Vec<Compound>,
+) -> Result<(Compound, BalancedReaction), StoichError> {
+    let side_elements = |side: &[Compound]| -> HashSet<Element> {
+        side.iter().flat_map(|c| c.atoms.keys().cloned()).collect()
+    };
+    let reagent_atoms = side_elements(&known_reagents);
+    let product_atoms = side_elements(&known_products);
+    let only_reagents: Vec<Element> =
+        reagent_atoms.difference(&product_atoms).cloned().collect();
+    let only_products: Vec<Element> =
+        product_atoms.difference(&reagent_atoms).cloned().collect();
+    let (required, missing_product) =
+        match (only_reagents.is_empty(), only_products.is_empty()) {
+            (false, true) => (only_reagents, true),
+            (true, false) => (only_products, false),
+            (false, false) => {
+                return Err(StoichError::InvalidInput(
+                    "Both sides have elements the other lacks, so more than \
+                     one compound is missing"
+                        .to_string(),
+                ))
+            }
+            (true, true) => {
+                return Err(StoichError::InvalidInput(
+                    "Cannot tell which side is missing a compound".to_string(),
+                ))
+            }
+        };
+    let mut shared: Vec<Element> = reagent_atoms
+        .intersection(&product_atoms)
         .cloned()
         .collect();
-    let elements: Vec<&Element> = all_atoms.iter().cloned().collect();
-    let mut all_compounds: Vec<Compound> = Vec::new();
-    let mut matrix: Vec<f64> = Vec::new();
-    debug!("Building matrix");
-    let mut push_atom = |input_compounds: &Vec<&Compound>,
-                         element: &Element| {
-        for compound in input_compounds {
-            all_compounds.push(compound.to_owned().to_owned());
-            let coefficient =
-                compound.atoms.get(element).cloned().unwrap_or(0 as u32);
-            trace!(
-                "Pushing {:?}*{:?} from {:?}",
-                coefficient,
-                element,
-                compound
+    shared.sort_by_key(|e| e.get_atomic_number());
+    let mut elements: Vec<Element> =
+        required.iter().chain(shared.iter()).cloned().collect();
+    elements.sort_by_key(|e| e.get_atomic_number());
+    let element_refs: Vec<&Element> = elements.iter().collect();
+    let compounds: Vec<&Compound> =
+        known_reagents.iter().chain(known_products.iter()).collect();
+    let base =
+        build_matrix(&element_refs, &compounds, known_reagents.len(), false);
+    let unknown_sign = match missing_product {
+        true => Rational::from(-1),
+        false => Rational::from(1),
+    };
+    for extra in 0..=shared.len() {
+        let mut found: Vec<Vec<Integer>> = vec![];
+        let mut candidates: Vec<Vec<Element>> = vec![];
+        for mask in 0..(1u32 << shared.len()) {
+            if mask.count_ones() as usize != extra {
+                continue;
+            }
+            let mut composition = required.clone();
+            composition.extend(
+                shared
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, e)| *e),
             );
-            matrix.push(coefficient as f64);
+            let matrix: Vec<Vec<Rational>> = elements
+                .iter()
+                .zip(base.iter())
+                .map(|(element, row)| {
+                    let mut row = row.clone();
+                    row.extend(composition.iter().map(|e| {
+                        match e == element {
+                            true => unknown_sign.clone(),
+                            false => Rational::new(),
+                        }
+                    }));
+                    row
+                })
+                .collect();
+            let columns = compounds.len() + composition.len();
+            let basis = null_space(matrix, columns);
+            trace!("Trying {:?}, null space {:?}", composition, basis);
+            match basis.as_slice() {
+                [solution] => {
+                    let ratios = integer_ratios(solution)?;
+                    let sign = ratios[0].cmp0();
+                    if sign != Ordering::Equal
+                        && ratios.iter().all(|r| r.cmp0() == sign)
+                    {
+                        found.push(ratios.into_iter().map(Integer::abs).collect());
+                        candidates.push(composition);
+                    }
+                }
+                [] => {}
+                _ => {
+                    return Err(StoichError::InvalidInput(format!(
+                        "Missing compound is ambiguous: containing {:?} leaves {} independent solutions",
+                        composition.iter().map(|e| e.get_symbol()).collect::<Vec<_>>(),
+                        basis.len()
+                    )))
+                }
+            }
         }
-    };
-    for element in all_atoms.iter() {
-        debug!("Getting coefficients for {:?}", element);
-        push_atom(&reagents.iter().collect(), element);
-        push_atom(&products.iter().collect(), element);
-    }
-    debug!("Constructing matrix");
-    let mx = DMatrix::from_row_slice(
-        elements.len(),
-        reagents.len() + products.len(),
-        matrix.as_slice(),
-    );
-    let c = reagents.len() + products.len() - 1;
-    let (a, b) = mx.columns_range_pair(0..c, c..);
-    let x = a.svd(true, true);
-    debug!("Solving equation system");
-    let solution = x.solve(&b, 0.0)?;
-    debug!("Solution: {:?}", solution);
-    let coefficients: Vec<f32> =
-        solution.column(0).iter().map(|c| *c as f32).collect();
-    debug!("Got solution coefficients: {:?}", &coefficients);
-    trace!("Converting to rationals");
-    let rational_coeffs: Vec<Rational> = coefficients
-        .iter()
-        .cloned()
-        .map(|c| {
-            trace!("Constructing rational from {:?}", &c);
-            Rational::from_f32(c).ok_or_else(|| {
-                format!("Could not construct rational from: {:?}", &c)
+        match found.len() {
+            0 => continue,
+            1 => {}
+            _ => {
+                return Err(StoichError::InvalidInput(format!(
+                    "Missing compound is ambiguous between compositions {:?}",
+                    candidates
+                        .iter()
+                        .map(|c| c.iter().map(|e| e.get_symbol()).collect())
+                        .collect::<Vec<Vec<&str>>>()
+                )))
+            }
+        }
+        // Free elements such as O2 are written in their diatomic form.
+        let atomicity = match candidates[0].as_slice() {
+            [e] if DIATOMIC.contains(&e.get_symbol()) => 2,
+            _ => 1,
+        };
+        let mut solution = found.swap_remove(0);
+        let counts = &solution[compounds.len()..];
+        let mut multiple =
+            counts.iter().fold(Integer::new(), |acc, n| acc.gcd(n));
+        if !multiple.is_divisible_u(atomicity) {
+            solution = solution.iter().map(|n| Integer::from(n * 2)).collect();
+            multiple *= 2;
+        }
+        multiple /= atomicity;
+        let counts = &solution[compounds.len()..];
+        let atoms: BTreeMap<Element, u32> = candidates[0]
+            .iter()
+            .zip(counts.iter())
+            .map(|(e, n)| {
+                to_coefficient(Integer::from(n / &multiple)).map(|n| (*e, n))
             })
-        })
-        .collect::<Result<Vec<Rational>, String>>()?;
-    trace!("Got rational coefficients: {:?}", &rational_coeffs);
-    trace!("Limiting denominators");
-    let rational_limited: Vec<Rational> = rational_coeffs
+            .collect::<Result<BTreeMap<Element, u32>, StoichError>>()?;
+        let missing = Compound::from_formula(&formula_string(&atoms))?;
+        debug!("Inferred missing compound {}", missing.formula);
+        let coefficients = solution[..compounds.len()]
+            .iter()
+            .cloned()
+            .map(to_coefficient)
+            .collect::<Result<Vec<u32>, StoichError>>()?;
+        let reactant = |i: usize| {
+            Reactant::of_compound(compounds[i].clone(), coefficients[i])
+        };
+        let mut reagents: Vec<Reactant> =
+            (0..known_reagents.len()).map(reactant).collect();
+        let mut products: Vec<Reactant> = (known_reagents.len()
+            ..compounds.len())
+            .map(reactant)
+            .collect();
+        let missing_reactant =
+            Reactant::of_compound(missing.clone(), to_coefficient(multiple)?);
+        match missing_product {
+            true => products.push(missing_reactant),
+            false => reagents.push(missing_reactant),
+        }
+        check_balance(&reagents, &products)?;
+        let mut reaction = BalancedReaction::new(reagents, products);
+        reduce_coefficients(&mut reaction);
+        return Ok((missing, reaction));
+    }
+    Err(StoichError::Unbalanceable(
+        "No single compound completes the equation".to_string(),
+    ))
+}
+
+/// Balance a redox half-reaction skeleton such as `Cr2O7^2- = Cr3+` by
+/// adding water, H+ (acidic) or OH- (basic) and electrons to whichever
+/// side needs them. Returns the reaction and the electrons transferred.
+pub fn balance_half_reaction(
+    reagents: Vec<Compound>,
+    products: Vec<Compound>,
+    medium: Medium,
+) -> Result<(BalancedReaction, u32), StoichError> {
+    let supplied: HashSet<Element> = ["H", "O"]
         .iter()
-        .cloned()
-        .map(|r| limit_denominator(&r.abs(), 100))
-        .collect::<Result<Vec<Rational>, String>>()?;
-    trace!("Limited rational coefficients: {:?}", rational_limited);
-    let denominators: Vec<u64> = rational_limited
+        .filter_map(|s| Element::from_symbol(s))
+        .collect();
+    let side_atoms = |side: &[Compound]| -> HashSet<Element> {
+        side.iter()
+            .flat_map(|c| c.atoms.keys().cloned())
+            .filter(|e| !supplied.contains(e))
+            .collect()
+    };
+    let (reagent_atoms, product_atoms) =
+        (side_atoms(&reagents), side_atoms(&products));
+    if reagent_atoms != product_atoms {
+        return Err(StoichError::Unbalanceable(format!(
+            "Half-reaction cannot be balanced. Elements on only one side = {:?}",
+            reagent_atoms.symmetric_difference(&product_atoms).collect::<Vec<_>>()
+        )));
+    }
+    let ion = match medium {
+        Medium::Acidic => "H+",
+        Medium::Basic => "OH-",
+    };
+    let added = [
+        Compound::from_formula("H2O")?,
+        Compound::from_formula(ion)?,
+        Compound::electron(),
+    ];
+    let compounds: Vec<&Compound> = reagents
         .iter()
-        .cloned()
-        .map(|c| {
-            c.denom().to_u64().ok_or_else(|| {
-                format!(
-                    "Could not convert denominator {:?} to u64!",
-                    &c.denom()
-                )
-            })
-        })
-        .collect::<Result<Vec<u64>, String>>()?;
-    trace!("Got denominators: {:?}", &denominators);
-    let scale: u64 = denominators
+        .chain(added.iter())
+        .chain(products.iter())
+        .collect();
+    let mut elements: Vec<&Element> = reagent_atoms
+        .union(&supplied)
+        .filter(|e| compounds.iter().any(|c| c.atoms.contains_key(e)))
+        .collect();
+    elements.sort();
+    let split = reagents.len() + added.len();
+    let matrix = build_matrix(&elements, &compounds, split, true);
+    debug!("Solving half-reaction system");
+    let basis = null_space(matrix, compounds.len());
+    debug!("Null space basis: {:?}", basis);
+    let solution = match basis.as_slice() {
+        [solution] => solution,
+        [] => {
+            return Err(StoichError::Unbalanceable(
+                "Half-reaction could not be balanced!".to_string(),
+            ))
+        }
+        _ => return Err(StoichError::Underdetermined(basis)),
+    };
+    let mut ratios = integer_ratios(solution)?;
+    if ratios[0].cmp0() == Ordering::Less {
+        ratios = ratios.into_iter().map(|r| -r).collect();
+    }
+    let skeleton_positive = ratios
         .iter()
-        .cloned()
-        .combinations(2)
-        .fold(1, |acc, cur| max(acc, lcm(cur[0] as u64, cur[1] as u64)));
-    debug!("Scaling coefficients by: {}", scale);
-    let mut scaled_coeffs: Vec<u64> = rational_limited
+        .enumerate()
+        .filter(|(i, _)| *i < reagents.len() || *i >= split)
+        .all(|(_, r)| r.cmp0() == Ordering::Greater);
+    if !skeleton_positive {
+        return Err(StoichError::Unbalanceable(format!(
+            "Half-reaction has no solution with positive coefficients: {:?}",
+            ratios
+        )));
+    }
+    let coefficients = ratios
         .iter()
-        .map(|f| f * Rational::from((scale, 1)))
-        .map(|f| {
-            f.numer().to_u64().ok_or_else(|| {
-                format!("Could not convert scaled {:?} to u64", &f.numer())
+        .map(|r| to_coefficient(r.clone().abs()))
+        .collect::<Result<Vec<u32>, StoichError>>()?;
+    let electrons = coefficients[split - 1];
+    let reactant =
+        |i: usize| Reactant::of_compound(compounds[i].clone(), coefficients[i]);
+    let mut reagents_result: Vec<Reactant> =
+        (0..reagents.len()).map(reactant).collect();
+    let mut products_result: Vec<Reactant> =
+        (split..compounds.len()).map(reactant).collect();
+    for (i, ratio) in ratios.iter().enumerate().take(split).skip(reagents.len())
+    {
+        match ratio.cmp0() {
+            Ordering::Greater => reagents_result.push(reactant(i)),
+            Ordering::Less => products_result.push(reactant(i)),
+            Ordering::Equal => {}
+        }
+    }
+    debug!("Half-reaction transfers {} electrons", electrons);
+    if check_balance(&reagents_result, &products_result).is_err() {
+        return Err(StoichError::Unbalanceable(
+            "Half-reaction could not be balanced!".to_string(),
+        ));
+    }
+    Ok((
+        BalancedReaction::new(reagents_result, products_result),
+        electrons,
+    ))
+}
+
+/// An element whose oxidation number changes over a reaction. Numbers are
+/// averaged over all of the element's atoms on each side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OxidationChange {
+    pub element: Element,
+    pub before: Rational,
+    pub after: Rational,
+}
+
+impl OxidationChange {
+    /// Electrons lost (when oxidized) or gained (when reduced) per atom.
+    pub fn electrons_per_atom(&self) -> Rational {
+        Rational::from(&self.after - &self.before).abs()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RedoxAnalysis {
+    pub oxidized: Vec<OxidationChange>,
+    pub reduced: Vec<OxidationChange>,
+}
+
+/// Find which elements of a balanced reaction are oxidized and which are
+/// reduced, from the oxidation numbers on each side.
+pub fn redox_analysis(
+    reaction: &BalancedReaction,
+) -> Result<RedoxAnalysis, StoichError> {
+    let average_states = |side: &[Reactant]| {
+        let mut totals: HashMap<Element, (i64, i64)> = HashMap::new();
+        for r in side {
+            for ((element, _), state) in oxidation_states(&r.compound)? {
+                let total = totals.entry(element).or_insert((0, 0));
+                total.0 += state as i64 * r.molar_coefficient as i64;
+                total.1 += r.molar_coefficient as i64;
+            }
+        }
+        Ok(totals
+            .into_iter()
+            .map(|(e, (sum, atoms))| (e, Rational::from((sum, atoms))))
+            .collect::<HashMap<Element, Rational>>())
+    };
+    let before: HashMap<Element, Rational> =
+        average_states(&reaction.reactants)?;
+    let after: HashMap<Element, Rational> = average_states(&reaction.products)?;
+    let mut changes: Vec<OxidationChange> = before
+        .into_iter()
+        .filter_map(|(element, before)| {
+            let after = after.get(&element)?.clone();
+            (after != before).then_some(OxidationChange {
+                element,
+                before,
+                after,
             })
         })
-        .collect::<Result<Vec<u64>, String>>()?;
-    scaled_coeffs.push(scale);
-    trace!("Got scaled coefficients: {:?}", scaled_coeffs);
-    let result: Vec<Reactant> = all_compounds
+        .collect();
+    if changes.is_empty() {
+        return Err(StoichError::InvalidInput(format!(
+            "{} is not a redox reaction",
+            reaction
+        )));
+    }
+    changes.sort_by_key(|c| c.element);
+    let (oxidized, reduced) =
+        changes.into_iter().partition(|c| c.after > c.before);
+    Ok(RedoxAnalysis { oxidized, reduced })
+}
+
+/// Reduce a molecular equation to its net ionic form: aqueous strong
+/// electrolytes are split into their ions and spectator ions, which appear
+/// unchanged on both sides, are cancelled.
+pub fn net_ionic(
+    reaction: &BalancedReaction,
+) -> Result<BalancedReaction, StoichError> {
+    let mut reagents = dissociate_side(&reaction.reactants)?;
+    let mut products = dissociate_side(&reaction.products)?;
+    cancel_spectators(&mut reagents, &mut products);
+    if reagents.is_empty() || products.is_empty() {
+        return Err(StoichError::Unbalanceable(
+            "Equation has no net ionic reaction".to_string(),
+        ));
+    }
+    if check_balance(&reagents, &products).is_err() {
+        return Err(StoichError::Unbalanceable(
+            "Net ionic equation could not be balanced!".to_string(),
+        ));
+    }
+    let mut result = BalancedReaction::new(reagents, products);
+    result.reversible = reaction.reversible;
+    reduce_coefficients(&mut result);
+    Ok(result)
+}
+
+/// The ions left unchanged by the reaction once aqueous strong
+/// electrolytes are dissociated, i.e. those `net_ionic` cancels.
+pub fn spectator_ions(
+    reaction: &BalancedReaction,
+) -> Result<Vec<Compound>, StoichError> {
+    let mut reagents = dissociate_side(&reaction.reactants)?;
+    let mut products = dissociate_side(&reaction.products)?;
+    Ok(cancel_spectators(&mut reagents, &mut products)
+        .into_iter()
+        .filter(|c| c.charge != 0)
+        .collect())
+}
+
+// Removes species common to both sides, returning those that were cancelled.
+fn cancel_spectators(
+    reagents: &mut Vec<Reactant>,
+    products: &mut Vec<Reactant>,
+) -> Vec<Compound> {
+    let mut cancelled = vec![];
+    for reagent in reagents.iter_mut() {
+        if let Some(product) =
+            products.iter_mut().find(|p| p.compound == reagent.compound)
+        {
+            let common =
+                reagent.molar_coefficient.min(product.molar_coefficient);
+            trace!("Cancelling {} {}", common, reagent.compound.formula);
+            reagent.molar_coefficient -= common;
+            product.molar_coefficient -= common;
+            cancelled.push(reagent.compound.clone());
+        }
+    }
+    reagents.retain(|r| r.molar_coefficient > 0);
+    products.retain(|p| p.molar_coefficient > 0);
+    cancelled
+}
+
+// Replaces each dissociating compound by its ions, merging repeated species.
+fn dissociate_side(side: &[Reactant]) -> Result<Vec<Reactant>, StoichError> {
+    let mut result: Vec<Reactant> = vec![];
+    for reactant in side {
+        let species = match dissociate(&reactant.compound)? {
+            Some(ions) => ions,
+            None => vec![(reactant.compound.clone(), 1)],
+        };
+        for (compound, count) in species {
+            let coefficient = count * reactant.molar_coefficient;
+            match result.iter_mut().find(|r| r.compound == compound) {
+                Some(existing) => existing.molar_coefficient += coefficient,
+                None => {
+                    result.push(Reactant::of_compound(compound, coefficient))
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+// Splits an aqueous strong electrolyte into its cation and anion with their
+// counts: soluble salts of metals or ammonium, and the strong acids.
+fn dissociate(
+    compound: &Compound,
+) -> Result<Option<Vec<(Compound, u32)>>, StoichError> {
+    if compound.state != Some(State::Aqueous) || compound.charge != 0 {
+        return Ok(None);
+    }
+    let hydrogen = Element::from_symbol("H");
+    let nitrogen = Element::from_symbol("N");
+    for formula in ANIONS.iter() {
+        let anion = Compound::from_formula(formula)?;
+        let mut n = 1;
+        while anion
+            .atoms
+            .iter()
+            .all(|(e, c)| compound.atoms.get(e).is_some_and(|t| *t >= c * n))
+        {
+            let remainder: Vec<(Element, u32)> = compound
+                .atoms
+                .iter()
+                .map(|(e, c)| (*e, c - anion.atoms.get(e).map_or(0, |a| a * n)))
+                .filter(|(_, c)| *c > 0)
+                .collect();
+            let total = n * anion.charge.unsigned_abs();
+            let cation = match remainder.as_slice() {
+                [(e, m)] if total % m == 0 => {
+                    let charge = total / m;
+                    let strong_acid = Some(*e) == hydrogen
+                        && charge == 1
+                        && STRONG_ACIDS.iter().any(|acid| {
+                            parse_formula(acid)
+                                .is_ok_and(|a| a == compound.atoms)
+                        });
+                    let metal = e.is_metal()
+                        && e.get_oxidation_states().contains(&(charge as i8));
+                    match (strong_acid || metal, charge) {
+                        (false, _) => None,
+                        (true, 1) => Some((format!("{}+", e.get_symbol()), *m)),
+                        (true, c) => {
+                            Some((format!("{}^{}+", e.get_symbol(), c), *m))
+                        }
+                    }
+                }
+                [(a, nh), (b, h)] | [(b, h), (a, nh)]
+                    if Some(*a) == nitrogen
+                        && Some(*b) == hydrogen
+                        && *h == 4 * nh
+                        && total == *nh =>
+                {
+                    Some(("NH4+".to_string(), *nh))
+                }
+                _ => None,
+            };
+            if let Some((cation, m)) = cation {
+                debug!(
+                    "{} dissociates into {} {} and {} {}",
+                    compound.formula, m, cation, n, formula
+                );
+                return Ok(Some(vec![
+                    (Compound::from_formula(&format!("{}(aq)", cation))?, m),
+                    (Compound::from_formula(&format!("{}(aq)", formula))?, n),
+                ]));
+            }
+            n += 1;
+        }
+    }
+    Ok(None)
+}
+
+// Net charge is balanced as one more element, with its own matrix row, as
+// soon as any species carries a charge.
+fn has_charge(compounds: &[&Compound]) -> bool {
+    compounds.iter().any(|c| c.charge != 0)
+}
+
+// Columns before `split` are reagents, the rest are products and enter the
+// matrix negated so that every solution is a null-space vector.
+fn build_matrix(
+    elements: &[&Element],
+    compounds: &[&Compound],
+    split: usize,
+    conserve_charge: bool,
+) -> Vec<Vec<Rational>> {
+    debug!("Building matrix");
+    let signed = |i: usize, value: Rational| match i < split {
+        true => value,
+        false => -value,
+    };
+    let mut matrix: Vec<Vec<Rational>> = elements
         .iter()
-        .map(|compound| compound.to_owned())
-        .zip(&mut scaled_coeffs.iter().map(|c| c.to_owned()))
-        .map(|(c, coeff)| Reactant::of_compound(c, coeff as u32))
+        .map(|element| {
+            debug!("Getting coefficients for {:?}", element);
+            compounds
+                .iter()
+                .enumerate()
+                .map(|(i, compound)| {
+                    let count =
+                        compound.atoms.get(element).cloned().unwrap_or(0);
+                    trace!(
+                        "Pushing {:?}*{:?} from {:?}",
+                        count,
+                        element,
+                        compound
+                    );
+                    signed(i, Rational::from(count))
+                })
+                .collect()
+        })
         .collect();
-    let (reagents_result, products_result) = result.split_at(reagents.len());
-    if check_balance(reagents_result, products_result)? {
-        let reaction = BalancedReaction::new(
-            reagents_result.to_vec(),
-            products_result.to_vec(),
+    if conserve_charge {
+        debug!("Adding charge row");
+        matrix.push(
+            compounds
+                .iter()
+                .enumerate()
+                .map(|(i, compound)| signed(i, Rational::from(compound.charge)))
+                .collect(),
         );
-        Ok(reaction)
-    } else {
-        Err(format!("Equation could not be balanced!"))
     }
+    matrix
+}
+
+// Reduces the matrix to reduced row echelon form, returning it along with
+// its pivot columns.
+fn row_reduce(
+    mut matrix: Vec<Vec<Rational>>,
+    columns: usize,
+) -> (Vec<Vec<Rational>>, Vec<usize>) {
+    let mut pivots: Vec<usize> = Vec::new();
+    for col in 0..columns {
+        let row = pivots.len();
+        let pivot = match (row..matrix.len()).find(|r| matrix[*r][col] != 0) {
+            Some(p) => p,
+            None => continue,
+        };
+        matrix.swap(row, pivot);
+        let lead = matrix[row][col].clone();
+        for value in matrix[row].iter_mut() {
+            *value /= &lead;
+        }
+        let pivot_row = matrix[row].clone();
+        for (r, other) in matrix.iter_mut().enumerate() {
+            if r == row || other[col] == 0 {
+                continue;
+            }
+            let factor = other[col].clone();
+            for (value, p) in other.iter_mut().zip(pivot_row.iter()) {
+                *value -= Rational::from(&factor * p);
+            }
+        }
+        trace!("Pivot at column {:?}, matrix: {:?}", col, matrix);
+        pivots.push(col);
+    }
+    (matrix, pivots)
+}
+
+// Returns one null-space basis vector per free column.
+pub(crate) fn null_space(
+    matrix: Vec<Vec<Rational>>,
+    columns: usize,
+) -> Vec<Vec<Rational>> {
+    let (matrix, pivots) = row_reduce(matrix, columns);
+    (0..columns)
+        .filter(|col| !pivots.contains(col))
+        .map(|free| {
+            let mut vector = vec![Rational::new(); columns];
+            vector[free] = Rational::from(1);
+            for (row, pivot) in pivots.iter().enumerate() {
+                vector[*pivot] = -matrix[row][free].clone();
+            }
+            vector
+        })
+        .collect()
+}
+
+// Scales a rational vector to the smallest integers with the same ratios,
+// keeping each entry's sign.
+fn integer_ratios(vector: &[Rational]) -> Result<Vec<Integer>, StoichError> {
+    if vector.iter().all(|r| r.cmp0() == Ordering::Equal) {
+        return Err(StoichError::Unbalanceable(
+            "Equation could not be balanced!".to_string(),
+        ));
+    }
+    let scale = vector
+        .iter()
+        .fold(Integer::from(1), |acc, r| acc.lcm(r.denom()));
+    trace!("Scaling coefficients by: {}", scale);
+    let scaled: Vec<Integer> = vector
+        .iter()
+        .map(|r| Integer::from(r.numer() * &scale) / r.denom())
+        .collect();
+    let divisor = scaled.iter().fold(Integer::new(), |acc, n| acc.gcd(n));
+    trace!("Reducing coefficients by: {}", divisor);
+    Ok(scaled.into_iter().map(|n| n / &divisor).collect())
+}
+
+fn to_coefficient(value: Integer) -> Result<u32, StoichError> {
+    value
+        .to_u32()
+        .ok_or_else(|| StoichError::Overflow(value.to_string()))
 }
 
+fn scale_to_integers(vector: &[Rational]) -> Result<Vec<u32>, StoichError> {
+    let ratios = integer_ratios(vector)?;
+    let sign = match ratios.iter().find(|r| r.cmp0() != Ordering::Equal) {
+        Some(r) => r.cmp0(),
+        None => {
+            return Err(StoichError::Unbalanceable(
+                "Equation could not be balanced!".to_string(),
+            ))
+        }
+    };
+    if ratios
+        .iter()
+        .any(|r| r.cmp0() != sign && r.cmp0() != Ordering::Equal)
+    {
+        return Err(StoichError::Unbalanceable(format!(
+            "Equation has no solution with positive coefficients: {:?}",
+            vector
+        )));
+    }
+    ratios
+        .into_iter()
+        .map(Integer::abs)
+        .map(to_coefficient)
+        .collect()
+}
+
+// Err naming each element, or the net charge, whose totals differ between
+// the two sides.
 fn check_balance(
     reactants: &[Reactant],
     products: &[Reactant],
-) -> Result<bool, String> {
+) -> Result<(), StoichError> {
     let react_elems: HashMap<Element, u64> = reactants
         .iter()
         .map(|s| (&s.compound.atoms, s.molar_coefficient))
@@ -173,70 +1268,66 @@ fn check_balance(
         "Checking balanced?: Reagent elements: {:?} === Product elements: {:?}",
         react_elems, prod_elems
     );
-    Ok(react_elems.eq(&prod_elems))
-}
-
-fn limit_denominator(
-    given: &Rational,
-    max_denominator: u64,
-) -> Result<Rational, String> {
-    debug!(
-        "Limiting denominator for {:?} to at most {:?}",
-        given, max_denominator
-    );
-    if max_denominator < 1 || given.denom() <= &max_denominator {
-        Ok(given.to_owned())
-    } else {
-        let (mut p0, mut q0, mut p1, mut q1) = (0u64, 1u64, 1u64, 0u64);
-        let (mut n, mut d) = (
-            given
-                .numer()
-                .to_u64()
-                .ok_or_else(|| format!("No numerator for: {:?}", given))?,
-            given
-                .denom()
-                .to_u64()
-                .ok_or_else(|| format!("No denominator for: {:?}", given))?,
-        );
-        let mut a: u64;
-        let mut q2: u64;
-        loop {
-            a = n / d;
-            q2 = q0 + (a * q1);
-            if q2 > max_denominator {
-                break;
+    let mut elements: Vec<&Element> =
+        react_elems.keys().chain(prod_elems.keys()).collect();
+    elements.sort();
+    elements.dedup();
+    let mut differences: Vec<String> = elements
+        .into_iter()
+        .filter_map(|e| {
+            let reagent = react_elems.get(e).cloned().unwrap_or(0);
+            let product = prod_elems.get(e).cloned().unwrap_or(0);
+            match reagent == product {
+                true => None,
+                false => Some(format!(
+                    "{} {} in reagents, {} in products",
+                    e.get_symbol(),
+                    reagent,
+                    product
+                )),
             }
-            let p0_new: u64 = p1;
-            let q0_new: u64 = q1;
-            let p1_new: u64 = p0 + (a * p1);
-            let q1_new: u64 = q2;
-            p0 = p0_new;
-            q0 = q0_new;
-            p1 = p1_new;
-            q1 = q1_new;
-            let n_new: u64 = d;
-            let d_new: u64 = n - (a * d);
-            n = n_new;
-            d = d_new;
-        }
-        let k = (max_denominator - q0) / q1;
-        let bound1: Rational = Rational::from((p0 + k * p1, q0 + k * q1));
-        let bound2: Rational = Rational::from((p1, q1));
-        let d1f: Rational = bound1.clone() - given;
-        let d2f: Rational = bound2.clone() - given;
-        if d1f.abs() <= d2f.abs() {
-            Ok(bound1)
-        } else {
-            Ok(bound2)
-        }
+        })
+        .collect();
+    let charge = |side: &[Reactant]| -> i64 {
+        side.iter()
+            .map(|r| r.compound.charge as i64 * r.molar_coefficient as i64)
+            .sum()
+    };
+    let (reagent_charge, product_charge) =
+        (charge(reactants), charge(products));
+    if reagent_charge != product_charge {
+        differences.push(format!(
+            "charge {} in reagents, {} in products",
+            reagent_charge, product_charge
+        ));
+    }
+    match differences.is_empty() {
+        true => Ok(()),
+        false => Err(StoichError::Unbalanceable(format!(
+            "Equation could not be balanced! {}",
+            differences.join("; ")
+        ))),
     }
 }
 
 #[cfg(test)]
 #[allow(non_snake_case)]
 mod tests {
+    use rug::Rational;
+
+    use crate::error::StoichError;
     use crate::model::*;
-    use crate::solve::balance;
+    use crate::parse::{parse_equation, parse_equation_with_coefficients};
+    #[cfg(feature = "parallel")]
+    use crate::solve::balance_each;
+    use crate::solve::{
+        balance, balance_batch, balance_combustion, balance_file,
+        balance_fixed, balance_from_reader, balance_half_reaction,
+        balance_rational, balance_result, balance_verbose,
+        balance_with_options, check_balance, infer_missing, net_ionic,
+        redox_analysis, reduce_coefficients, spectator_ions, BalanceOptions,
+        BalanceResult, Medium,
+    };
 
     macro_rules! parse_balanced_reagent {
         (($subst:tt, $coef: tt)) => {
@@ -308,6 +1399,162 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_C57H110O6() {
+        expect_balanced!(
+            C57H110O6 + O2 = CO2 + H2O =>
+                (C57H110O6, 2) + (O2, 163) = (CO2, 114) + (H2O, 110)
+        );
+    }
+
+    #[test]
+    fn test_K4FeCN6() {
+        // K4Fe(CN)6 + KMnO4 + H2SO4 = KHSO4 + Fe2(SO4)3 + MnSO4 + HNO3 + CO2 + H2O
+        let rg = vec!["K4Fe(CN)6", "KMnO4", "H2SO4"];
+        let pd = vec!["KHSO4", "Fe2(SO4)3", "MnSO4", "HNO3", "CO2", "H2O"];
+        let result =
+            balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd))
+                .unwrap();
+        let coefficients: Vec<u32> = result
+            .reactants
+            .iter()
+            .chain(result.products.iter())
+            .map(|r| r.molar_coefficient)
+            .collect();
+        assert_eq!(coefficients, vec![10, 122, 299, 162, 5, 122, 60, 60, 188]);
+    }
+
+    #[test]
+    fn test_chromium_urea_complex() {
+        // [Cr(N2H4CO)6]4[Cr(CN)6]3 + KMnO4 + H2SO4
+        //   = K2Cr2O7 + MnSO4 + CO2 + KNO3 + K2SO4 + H2O
+        let rg = vec!["[Cr(N2H4CO)6]4[Cr(CN)6]3", "KMnO4", "H2SO4"];
+        let pd = vec!["K2Cr2O7", "MnSO4", "CO2", "KNO3", "K2SO4", "H2O"];
+        let result =
+            balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd))
+                .unwrap();
+        let coefficients: Vec<u32> = result
+            .reactants
+            .iter()
+            .chain(result.products.iter())
+            .map(|r| r.molar_coefficient)
+            .collect();
+        assert_eq!(
+            coefficients,
+            vec![10, 1176, 1399, 35, 1176, 420, 660, 223, 1879]
+        );
+    }
+
+    #[test]
+    fn test_coefficient_overflow() {
+        let vector = [Rational::from(1), Rational::from((1u64 << 40, 3))];
+        let result = super::scale_to_integers(&vector);
+        assert_eq!(
+            result.unwrap_err(),
+            StoichError::Overflow((1u64 << 40).to_string())
+        );
+        let vector = [Rational::from((1, 1u64 << 40)), Rational::from(1)];
+        assert!(super::scale_to_integers(&vector).is_err());
+    }
+
+    #[test]
+    fn test_balance_verbose() {
+        let (reaction, steps) = balance_verbose(
+            _formulas_to_compounds(vec!["H2", "O2"]),
+            _formulas_to_compounds(vec!["H2O"]),
+        )
+        .unwrap();
+        assert_eq!(reaction.to_string(), "2 H2 + O2 -> 2 H2O");
+        let r = |values: &[(i32, i32)]| -> Vec<Rational> {
+            values.iter().map(|v| Rational::from(*v)).collect()
+        };
+        assert_eq!(steps.elements, vec!["H", "O"]);
+        assert_eq!(steps.compounds, vec!["H2", "O2", "H2O"]);
+        assert_eq!(
+            steps.matrix,
+            vec![r(&[(2, 1), (0, 1), (-2, 1)]), r(&[(0, 1), (2, 1), (-1, 1)])]
+        );
+        assert_eq!(
+            steps.reduced,
+            vec![r(&[(1, 1), (0, 1), (-1, 1)]), r(&[(0, 1), (1, 1), (-1, 2)])]
+        );
+        assert_eq!(steps.null_space, r(&[(1, 1), (1, 2), (1, 1)]));
+        assert_eq!(steps.scale, 2);
+        let printed = steps.to_string();
+        assert!(printed.contains("Null space: [1, 1/2, 1]"), "{}", printed);
+        assert!(printed.ends_with("Scale: 2"), "{}", printed);
+    }
+
+    fn _fixed(equation: &str) -> Result<BalancedReaction, StoichError> {
+        let (rg, pd) = parse_equation_with_coefficients(equation).unwrap();
+        balance_fixed(rg, pd)
+    }
+
+    #[test]
+    fn test_fixed_coefficients() {
+        let to_string = |e: &str| _fixed(e).unwrap().to_string();
+        assert_eq!(to_string("2 H2 + O2 = H2O"), "2 H2 + O2 -> 2 H2O");
+        assert_eq!(to_string("4 H2 + O2 = H2O"), "4 H2 + 2 O2 -> 4 H2O");
+        assert_eq!(to_string("H2 + O2 = 4 H2O"), "4 H2 + 2 O2 -> 4 H2O");
+        assert_eq!(to_string("H2 + O2 = H2O"), "2 H2 + O2 -> 2 H2O");
+        assert!(_fixed("2 H2 + 2 O2 = H2O")
+            .unwrap_err()
+            .to_string()
+            .contains("inconsistent"));
+        assert!(_fixed("1 H2 + O2 = H2O")
+            .unwrap_err()
+            .to_string()
+            .contains("1/2"));
+    }
+
+    #[test]
+    fn test_fixed_coefficients_underdetermined() {
+        let result = _fixed("2 H2 + O2 = H2O + H2O2");
+        assert!(matches!(result, Err(StoichError::Underdetermined(_))));
+        let result = _fixed("4 H2 + 3 O2 = H2O + H2O2").unwrap();
+        assert_eq!(result.to_string(), "4 H2 + 3 O2 -> 2 H2O + 2 H2O2");
+    }
+
+    #[test]
+    fn test_redox_permanganate() {
+        let (rg, pd) =
+            parse_equation("MnO4^- + Fe^2+ + H^+ = Mn^2+ + Fe^3+ + H2O")
+                .unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        let analysis = redox_analysis(&reaction).unwrap();
+        assert_eq!(analysis.oxidized.len(), 1);
+        assert_eq!(analysis.oxidized[0].element.get_symbol(), "Fe");
+        assert_eq!(analysis.oxidized[0].electrons_per_atom(), 1);
+        assert_eq!(analysis.reduced.len(), 1);
+        assert_eq!(analysis.reduced[0].element.get_symbol(), "Mn");
+        assert_eq!(analysis.reduced[0].before, 7);
+        assert_eq!(analysis.reduced[0].after, 2);
+        assert_eq!(analysis.reduced[0].electrons_per_atom(), 5);
+    }
+
+    #[test]
+    fn test_not_redox() {
+        let (rg, pd) = parse_equation("NaOH + HCl = NaCl + H2O").unwrap();
+        let reaction = balance(rg, pd).unwrap();
+        let err = redox_analysis(&reaction).unwrap_err();
+        assert!(err.to_string().contains("not a redox"), "{}", err);
+    }
+
+    #[test]
+    fn test_zero_coefficient() {
+        let rg = vec!["NaOH", "HCl"];
+        let pd = vec!["NaCl", "H2O", "Cl2"];
+        let result =
+            balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd));
+        assert_eq!(
+            result.unwrap_err(),
+            StoichError::Unbalanceable(
+                "Equation only balances with a zero coefficient for Cl2"
+                    .to_string()
+            )
+        );
+    }
+
     #[test]
     fn test_missing_products() {
         //Fe3 + Cl5 = Cl2Fe5H2O
@@ -317,8 +1564,36 @@ mod tests {
             balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd));
         assert!(
             result.is_err(),
-            format!("Balance solution was not Err: {:?}", result),
-        )
+            "Balance solution was not Err: {:?}",
+            result
+        );
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Equation cannot be balanced. Product elements missing from \
+             reagents: H, O"
+        );
+        assert_eq!(
+            err,
+            StoichError::UnbalanceableElements {
+                missing_products: vec![],
+                missing_reagents: vec!["H".to_string(), "O".to_string()],
+            }
+        );
+        let (rg, pd) = parse_equation("NaCl + K = KCl + H2").unwrap();
+        assert_eq!(
+            balance(rg, pd).unwrap_err().to_string(),
+            "Equation cannot be balanced. Reagent elements missing from \
+             products: Na. Product elements missing from reagents: H"
+        );
+        let (rg, pd) = parse_equation("^13CH4 + O2 = CO2 + H2O").unwrap();
+        assert_eq!(
+            balance(rg, pd).unwrap_err(),
+            StoichError::UnbalanceableElements {
+                missing_products: vec!["^13C".to_string()],
+                missing_reagents: vec!["C".to_string()],
+            }
+        );
     }
 
     #[test]
@@ -330,7 +1605,477 @@ mod tests {
             balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd));
         assert!(
             result.is_err(),
-            format!("Balance solution was not Err: {:?}", result),
+            "Balance solution was not Err: {:?}",
+            result
+        )
+    }
+
+    #[test]
+    fn test_underdetermined() {
+        // C + O2 = CO + CO2
+        let rg = vec!["C", "O2"];
+        let pd = vec!["CO", "CO2"];
+        let result = balance_result(
+            _formulas_to_compounds(rg.clone()),
+            _formulas_to_compounds(pd.clone()),
         )
+        .unwrap();
+        match result {
+            BalanceResult::Underdetermined(basis) => {
+                assert_eq!(basis.len(), 2);
+                assert!(basis.iter().all(|v| v.len() == 4));
+            }
+            BalanceResult::Balanced(r) => panic!("Got {:?}", r),
+        }
+        let result =
+            balance(_formulas_to_compounds(rg), _formulas_to_compounds(pd));
+        let err = result.unwrap_err();
+        assert!(matches!(err, StoichError::Underdetermined(_)));
+        assert!(err.to_string().contains("2 independent solutions"));
+    }
+
+    #[test]
+    fn test_balance_batch() {
+        let results = balance_batch(&[
+            "H2 + O2 = H2O",
+            "H2 + Xx = H2Xx",
+            "Fe + O2 = Fe2O3",
+            "N2 + H2 <=> NH3",
+        ]);
+        assert_eq!(results.len(), 4);
+        assert_eq!(
+            results[0].as_ref().unwrap().to_string(),
+            "2 H2 + O2 -> 2 H2O"
+        );
+        assert!(results[1].is_err());
+        assert_eq!(
+            results[2].as_ref().unwrap().to_string(),
+            "4 Fe + 3 O2 -> 2 Fe2O3"
+        );
+        assert_eq!(
+            results[3].as_ref().unwrap().to_string(),
+            "N2 + 3 H2 <=> 2 NH3"
+        );
+        assert!(balance_batch(&[]).is_empty());
+    }
+
+    fn _batch_equations(count: usize) -> Vec<&'static str> {
+        [
+            "C6H12O6 + O2 = CO2 + H2O",
+            "KMnO4 + HCl = KCl + MnCl2 + H2O + Cl2",
+            "Fe + Cl2 = FeCl3",
+            "H2 + Xx = H2Xx",
+            "K4Fe(CN)6 + KMnO4 + H2SO4 = \
+             KHSO4 + Fe2(SO4)3 + MnSO4 + HNO3 + CO2 + H2O",
+        ]
+        .iter()
+        .cycle()
+        .take(count)
+        .cloned()
+        .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_balance_batch_parallel() {
+        let equations = _batch_equations(23);
+        let parallel = balance_batch(&equations);
+        assert_eq!(parallel, balance_each(&equations));
+        assert!(balance_batch(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_species_on_both_sides() {
+        let (rg, pd) = parse_equation("CH4 + O2 + H2O = CO2 + H2O").unwrap();
+        let err = balance(rg, pd).unwrap_err();
+        assert!(err.to_string().contains("H2O on both sides"), "{}", err);
+        let (rg, pd) = parse_equation("H2O2 + MnO2 = H2O + O2 + MnO2").unwrap();
+        let err = balance_result(rg, pd).unwrap_err();
+        assert!(err.to_string().contains("MnO2 on both sides"), "{}", err);
+    }
+
+    #[test]
+    fn test_balance_rational() {
+        // 2 H2 + O2 = 2 H2O
+        let coefficients = balance_rational(
+            _formulas_to_compounds(vec!["H2", "O2"]),
+            _formulas_to_compounds(vec!["H2O"]),
+        )
+        .unwrap();
+        assert_eq!(
+            coefficients,
+            vec![Rational::from(1), Rational::from((1, 2)), Rational::from(1)]
+        );
+        let result = balance_rational(
+            _formulas_to_compounds(vec!["C", "O2"]),
+            _formulas_to_compounds(vec!["CO", "CO2"]),
+        );
+        assert!(matches!(result, Err(StoichError::Underdetermined(_))));
+    }
+
+    #[test]
+    fn test_balance_from_reader() {
+        let input = "# Combustion\nCH4 + O2 = CO2 + H2O\n\n  \nH2 + O2 = H2O\n\
+                     # Not an equation\nNaCl = Cl2\n";
+        let results = balance_from_reader(input.as_bytes()).unwrap();
+        let lines: Vec<usize> = results.iter().map(|(n, _)| *n).collect();
+        assert_eq!(lines, vec![2, 5, 7]);
+        assert_eq!(
+            results[0].1.as_ref().unwrap().to_string(),
+            "CH4 + 2 O2 -> CO2 + 2 H2O"
+        );
+        assert_eq!(
+            results[1].1.as_ref().unwrap().to_string(),
+            "2 H2 + O2 -> 2 H2O"
+        );
+        assert!(results[2].1.is_err());
+    }
+
+    #[test]
+    fn test_balance_file() {
+        let path = std::env::temp_dir()
+            .join(format!("stoichkit-{}.txt", std::process::id()));
+        std::fs::write(&path, "# Rust\nFe + O2 = Fe2O3\n").unwrap();
+        let results = balance_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        let results = results.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 2);
+        assert_eq!(
+            results[0].1.as_ref().unwrap().to_string(),
+            "4 Fe + 3 O2 -> 2 Fe2O3"
+        );
+        assert!(balance_file(path).is_err());
+    }
+
+    #[test]
+    fn test_balance_with_options() {
+        let equation = "K4Fe(CN)6 + KMnO4 + H2SO4 = \
+                        KHSO4 + Fe2(SO4)3 + MnSO4 + HNO3 + CO2 + H2O";
+        let (rg, pd) = parse_equation(equation).unwrap();
+        let capped = BalanceOptions {
+            max_coefficient: Some(100),
+        };
+        let result = balance_with_options(rg.clone(), pd.clone(), &capped);
+        assert!(matches!(result, Err(StoichError::Unbalanceable(_))));
+        let raised = BalanceOptions {
+            max_coefficient: Some(300),
+        };
+        let reaction =
+            balance_with_options(rg.clone(), pd.clone(), &raised).unwrap();
+        assert_eq!(reaction.reactants[2].molar_coefficient, 299);
+        assert_eq!(reaction, balance(rg, pd).unwrap());
+        let (rg, pd) = parse_equation("Fe3+ + I- = Fe2+ + I2").unwrap();
+        assert_eq!(
+            balance_with_options(rg, pd, &BalanceOptions::default())
+                .unwrap()
+                .to_string(),
+            "2 Fe3+ + 2 I- -> 2 Fe2+ + I2"
+        );
+    }
+
+    #[test]
+    fn test_check_balance_reports_elements() {
+        let reagents = vec![
+            Reactant::from_formula("H2", 1).unwrap(),
+            Reactant::from_formula("O2", 1).unwrap(),
+        ];
+        let products = vec![Reactant::from_formula("H2O", 1).unwrap()];
+        assert_eq!(
+            check_balance(&reagents, &products),
+            Err(StoichError::Unbalanceable(
+                "Equation could not be balanced! O 2 in reagents, 1 in products"
+                    .to_string()
+            ))
+        );
+        let products = vec![Reactant::from_formula("H2O", 3).unwrap()];
+        assert_eq!(
+            check_balance(&reagents, &products),
+            Err(StoichError::Unbalanceable(
+                "Equation could not be balanced! H 2 in reagents, 6 in \
+                 products; O 2 in reagents, 3 in products"
+                    .to_string()
+            ))
+        );
+        let reagents = vec![Reactant::from_formula("Fe3+", 1).unwrap()];
+        let products = vec![Reactant::from_formula("Fe2+", 1).unwrap()];
+        assert_eq!(
+            check_balance(&reagents, &products),
+            Err(StoichError::Unbalanceable(
+                "Equation could not be balanced! charge 3 in reagents, 2 in \
+                 products"
+                    .to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_single_compound_side() {
+        let balanced = |equation: &str| {
+            let (rg, pd) = parse_equation(equation).unwrap();
+            balance(rg, pd).unwrap().to_string()
+        };
+        assert_eq!(balanced("CaCO3 = CaO + CO2"), "CaCO3 -> CaO + CO2");
+        assert_eq!(balanced("KClO3 = KCl + O2"), "2 KClO3 -> 2 KCl + 3 O2");
+        assert_eq!(balanced("H2O2 = H2O + O2"), "2 H2O2 -> 2 H2O + O2");
+        assert_eq!(balanced("Na + Cl2 = NaCl"), "2 Na + Cl2 -> 2 NaCl");
+        assert_eq!(balanced("N2 + H2 = NH3"), "N2 + 3 H2 -> 2 NH3");
+        assert_eq!(balanced("O2 = O3"), "3 O2 -> 2 O3");
+    }
+
+    #[test]
+    fn test_balance_conserves_mass() {
+        let equations = [
+            "C3H8 + O2 = CO2 + H2O",
+            "KMnO4 + HCl = KCl + MnCl2 + H2O + Cl2",
+            "Fe2O3 + CO = Fe + CO2",
+        ];
+        for reaction in balance_batch(&equations) {
+            reaction.unwrap().verify_mass_conservation().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_reduce_coefficients() {
+        let mut reaction = BalancedReaction::new(
+            vec![
+                Reactant::from_formula("H2", 4).unwrap(),
+                Reactant::from_formula("O2", 2).unwrap(),
+            ],
+            vec![Reactant::from_formula("H2O", 4).unwrap()],
+        );
+        reduce_coefficients(&mut reaction);
+        assert_eq!(reaction.to_string(), "2 H2 + O2 -> 2 H2O");
+        let (rg, pd) = parse_equation("8 Al + 12 Cl2 = 8 AlCl3").unwrap();
+        let balanced = balance(rg, pd).unwrap();
+        assert_eq!(balanced.to_string(), "2 Al + 3 Cl2 -> 2 AlCl3");
+    }
+
+    #[test]
+    fn test_unique_balance_result() {
+        let result = balance_result(
+            _formulas_to_compounds(vec!["H2", "O2"]),
+            _formulas_to_compounds(vec!["H2O"]),
+        )
+        .unwrap();
+        assert!(matches!(result, BalanceResult::Balanced(_)));
+    }
+
+    fn _ionic_coefficients(equation: &str) -> Result<Vec<u32>, StoichError> {
+        let (rg, pd) = parse_equation(equation)?;
+        let result = balance(rg, pd)?;
+        Ok(result
+            .reactants
+            .iter()
+            .chain(result.products.iter())
+            .map(|r| r.molar_coefficient)
+            .collect())
+    }
+
+    #[test]
+    fn test_ionic_permanganate() {
+        let result =
+            _ionic_coefficients("MnO4^- + Fe2+ + H+ = Mn2+ + Fe3+ + H2O");
+        assert_eq!(result.unwrap(), vec![1, 5, 8, 1, 5, 4]);
+    }
+
+    #[test]
+    fn test_ionic_dichromate() {
+        let result =
+            _ionic_coefficients("Cr2O7^2- + Fe2+ + H+ = Cr3+ + Fe3+ + H2O");
+        assert_eq!(result.unwrap(), vec![1, 6, 14, 2, 6, 7]);
+    }
+
+    #[test]
+    fn test_ionic_copper_nitrate() {
+        let result = _ionic_coefficients("Cu + NO3- + H+ = Cu2+ + NO + H2O");
+        assert_eq!(result.unwrap(), vec![3, 2, 8, 3, 2, 4]);
+    }
+
+    #[test]
+    fn test_ionic_unbalanceable_charge() {
+        let result = _ionic_coefficients("Na+ = Na");
+        assert!(result.is_err(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_balance_conserves_charge() {
+        let balanced = |equation: &str| {
+            let (rg, pd) = parse_equation(equation).unwrap();
+            balance(rg, pd).map(|r| r.to_string())
+        };
+        assert_eq!(
+            balanced("MnO4- + Fe2+ + H+ = Mn2+ + Fe3+ + H2O").unwrap(),
+            "MnO4- + 5 Fe2+ + 8 H+ -> Mn2+ + 5 Fe3+ + 4 H2O"
+        );
+        assert_eq!(
+            balanced("Fe3+ + I- = Fe2+ + I2").unwrap(),
+            "2 Fe3+ + 2 I- -> 2 Fe2+ + I2"
+        );
+        assert!(balanced("Na+ = Na").is_err());
+        let (rg, pd) = parse_equation("Ag+ + Cu = Ag + Cu2+").unwrap();
+        let (reaction, steps) = balance_verbose(rg, pd).unwrap();
+        assert_eq!(reaction.to_string(), "2 Ag+ + Cu -> 2 Ag + Cu2+");
+        assert_eq!(steps.elements, vec!["Ag", "Cu", "charge"]);
+        assert_eq!(steps.matrix.len(), 3);
+    }
+
+    fn _half_reaction(equation: &str, medium: Medium) -> (String, u32) {
+        let (rg, pd) = parse_equation(equation).unwrap();
+        let (result, electrons) =
+            balance_half_reaction(rg, pd, medium).unwrap();
+        (result.display_string(), electrons)
+    }
+
+    #[test]
+    fn test_half_reaction_dichromate_acidic() {
+        let (result, electrons) =
+            _half_reaction("Cr2O7^2- = Cr3+", Medium::Acidic);
+        assert_eq!(result, "1 Cr2O7^2- + 14 H+ + 6 e- = 2 Cr3+ + 7 H2O");
+        assert_eq!(electrons, 6);
+    }
+
+    #[test]
+    fn test_half_reaction_permanganate_basic() {
+        let (result, electrons) =
+            _half_reaction("MnO4^- = MnO2", Medium::Basic);
+        assert_eq!(result, "1 MnO4^- + 2 H2O + 3 e- = 1 MnO2 + 4 OH-");
+        assert_eq!(electrons, 3);
+    }
+
+    #[test]
+    fn test_half_reaction_oxidation() {
+        let (result, electrons) = _half_reaction("Fe2+ = Fe3+", Medium::Acidic);
+        assert_eq!(result, "1 Fe2+ = 1 Fe3+ + 1 e-");
+        assert_eq!(electrons, 1);
+    }
+
+    #[test]
+    fn test_half_reaction_missing_element() {
+        let (rg, pd) = parse_equation("Cr2O7^2- = Mn2+").unwrap();
+        let result = balance_half_reaction(rg, pd, Medium::Acidic);
+        assert!(result.is_err(), "{:?}", result);
+    }
+
+    fn _net_ionic(equation: &str) -> String {
+        let (rg, pd) = parse_equation(equation).unwrap();
+        net_ionic(&balance(rg, pd).unwrap()).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_net_ionic_precipitate() {
+        assert_eq!(
+            _net_ionic("AgNO3(aq) + NaCl(aq) = AgCl(s) + NaNO3(aq)"),
+            "Ag+(aq) + Cl-(aq) -> AgCl(s)"
+        );
+        assert_eq!(
+            _net_ionic("Ba(NO3)2(aq) + Na2SO4(aq) = BaSO4(s) + NaNO3(aq)"),
+            "Ba^2+(aq) + SO4^2-(aq) -> BaSO4(s)"
+        );
+    }
+
+    #[test]
+    fn test_net_ionic_acids() {
+        assert_eq!(
+            _net_ionic("H2SO4(aq) + KOH(aq) = K2SO4(aq) + H2O(l)"),
+            "H+(aq) + OH-(aq) -> H2O(l)"
+        );
+        assert_eq!(
+            _net_ionic("CH3COOH(aq) + NaOH(aq) = NaC2H3O2(aq) + H2O(l)"),
+            "CH3COOH(aq) + OH-(aq) -> C2H3O2-(aq) + H2O(l)"
+        );
+    }
+
+    #[test]
+    fn test_net_ionic_no_reaction() {
+        let (rg, pd) =
+            parse_equation("NaCl(aq) + KNO3(aq) = NaNO3(aq) + KCl(aq)")
+                .unwrap();
+        let result = net_ionic(&balance(rg, pd).unwrap());
+        assert!(result.is_err(), "{:?}", result);
+    }
+
+    #[test]
+    fn test_spectator_ions() {
+        let (rg, pd) =
+            parse_equation("Ba(NO3)2(aq) + Na2SO4(aq) = BaSO4(s) + NaNO3(aq)")
+                .unwrap();
+        let spectators = spectator_ions(&balance(rg, pd).unwrap()).unwrap();
+        let ions: Vec<(&str, i32)> = spectators
+            .iter()
+            .map(|c| (c.formula.as_str(), c.charge))
+            .collect();
+        assert_eq!(ions, vec![("NO3-(aq)", -1), ("Na+(aq)", 1)]);
+        let (rg, pd) =
+            parse_equation("HCl(aq) + NaOH(aq) = NaCl(aq) + H2O(l)").unwrap();
+        let spectators = spectator_ions(&balance(rg, pd).unwrap()).unwrap();
+        assert_eq!(spectators.len(), 2);
+        assert!(spectators.iter().all(|c| c.charge != 0));
+    }
+
+    fn _combustion(fuel: &str) -> Result<String, StoichError> {
+        balance_combustion(Compound::from_formula(fuel).unwrap())
+            .map(|r| r.to_string())
+    }
+
+    #[test]
+    fn test_balance_combustion() {
+        assert_eq!(
+            _combustion("C3H8").unwrap(),
+            "C3H8 + 5 O2 -> 3 CO2 + 4 H2O"
+        );
+        assert_eq!(
+            _combustion("C2H5OH").unwrap(),
+            "C2H5OH + 3 O2 -> 2 CO2 + 3 H2O"
+        );
+        assert_eq!(
+            _combustion("CH4S").unwrap(),
+            "CH4S + 3 O2 -> CO2 + 2 H2O + SO2"
+        );
+        assert_eq!(
+            _combustion("CH3NH2").unwrap(),
+            "4 CH3NH2 + 9 O2 -> 4 CO2 + 10 H2O + 2 N2"
+        );
+        assert!(_combustion("NaCl")
+            .unwrap_err()
+            .to_string()
+            .contains("route"));
+    }
+
+    fn _infer(equation: &str) -> Result<(String, String), StoichError> {
+        let (rg, pd) = parse_equation(equation).unwrap();
+        infer_missing(rg, pd).map(|(c, r)| (c.formula, r.to_string()))
+    }
+
+    #[test]
+    fn test_infer_missing() {
+        assert_eq!(
+            _infer("CaCO3 = CaO").unwrap(),
+            ("CO2".to_string(), "CaCO3 -> CaO + CO2".to_string())
+        );
+        assert_eq!(
+            _infer("NaOH + HCl = NaCl").unwrap(),
+            ("H2O".to_string(), "NaOH + HCl -> NaCl + H2O".to_string())
+        );
+        assert_eq!(
+            _infer("Fe2O3 = Fe").unwrap(),
+            ("O2".to_string(), "2 Fe2O3 -> 4 Fe + 3 O2".to_string())
+        );
+        assert_eq!(
+            _infer("Na = NaCl").unwrap(),
+            ("Cl2".to_string(), "2 Na + Cl2 -> 2 NaCl".to_string())
+        );
+    }
+
+    #[test]
+    fn test_infer_missing_ambiguous() {
+        let err = _infer("H2 + O2 = H2O").unwrap_err();
+        assert!(err.to_string().contains("which side"), "{}", err);
+        let err = _infer("Na + Cl2 = K").unwrap_err();
+        assert!(err.to_string().contains("more than one"), "{}", err);
+        let err =
+            infer_missing(_formulas_to_compounds(vec!["H2", "O2"]), vec![])
+                .unwrap_err();
+        assert!(err.to_string().contains("ambiguous"), "{}", err);
     }
 }
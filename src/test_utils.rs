@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use crate::model::Element;
 
 #[allow(dead_code)]
-pub fn e(expected: HashMap<&str, u32>) -> HashMap<Element, u32> {
+pub fn e(expected: HashMap<&str, u32>) -> BTreeMap<Element, u32> {
     expected
         .iter()
         .map(|p| (Element::from_symbol(p.0).unwrap(), p.1.to_owned()))
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+#[cfg(feature = "balance")]
+use rug::Rational;
+
+use crate::error::StoichError;
+use crate::model::{BalancedReaction, Reactant};
+#[cfg(feature = "balance")]
+use crate::solve::null_space;
+
+/// The standard enthalpy of reaction from standard enthalpies of formation
+/// keyed by formula as written in the reaction, e.g. `H2O(l)`. Elements in
+/// their standard states need an entry of zero like any other species.
+pub fn enthalpy_of_reaction(
+    reaction: &BalancedReaction,
+    formation: &HashMap<String, f64>,
+) -> Result<f64, StoichError> {
+    let side = |side: &[Reactant]| -> Result<f64, StoichError> {
+        side.iter()
+            .map(|r| {
+                formation
+                    .get(&r.compound.formula)
+                    .map(|h| h * r.molar_coefficient as f64)
+                    .ok_or_else(|| {
+                        StoichError::InvalidInput(format!(
+                            "No enthalpy of formation for {}",
+                            r.compound.formula
+                        ))
+                    })
+            })
+            .sum()
+    };
+    Ok(side(&reaction.products)? - side(&reaction.reactants)?)
+}
+
+// Net moles of each species a reaction produces, with reagents negative.
+#[cfg(feature = "balance")]
+fn net_species(reaction: &BalancedReaction) -> HashMap<&str, i64> {
+    let mut net = HashMap::new();
+    for r in &reaction.reactants {
+        *net.entry(r.compound.formula.as_str()).or_insert(0) -=
+            r.molar_coefficient as i64;
+    }
+    for p in &reaction.products {
+        *net.entry(p.compound.formula.as_str()).or_insert(0) +=
+            p.molar_coefficient as i64;
+    }
+    net
+}
+
+/// The enthalpy change of `target` by Hess's law, from reactions with known
+/// enthalpy changes that combine to give it. Species are matched by formula
+/// as written, so states must agree.
+#[cfg(feature = "balance")]
+pub fn hess_law(
+    steps: &[(BalancedReaction, f64)],
+    target: &BalancedReaction,
+) -> Result<f64, StoichError> {
+    let nets: Vec<HashMap<&str, i64>> = steps
+        .iter()
+        .map(|(reaction, _)| net_species(reaction))
+        .chain(std::iter::once(net_species(target)))
+        .collect();
+    let mut species: Vec<&str> =
+        nets.iter().flat_map(|net| net.keys().cloned()).collect();
+    species.sort_unstable();
+    species.dedup();
+    // Columns are the steps followed by the negated target.
+    let columns = nets.len();
+    let matrix: Vec<Vec<Rational>> = species
+        .iter()
+        .map(|s| {
+            nets.iter()
+                .enumerate()
+                .map(|(i, net)| {
+                    let n = net.get(s).cloned().unwrap_or(0);
+                    match i == columns - 1 {
+                        true => Rational::from(-n),
+                        false => Rational::from(n),
+                    }
+                })
+                .collect()
+        })
+        .collect();
+    let combination = null_space(matrix, columns)
+        .into_iter()
+        .find(|v| v[columns - 1] != 0)
+        .ok_or_else(|| {
+            StoichError::InvalidInput(format!(
+                "{} is not a combination of the given reactions",
+                target
+            ))
+        })?;
+    debug!("Hess's law combination: {:?}", combination);
+    let scale = combination[columns - 1].clone();
+    Ok(steps
+        .iter()
+        .zip(combination)
+        .map(|((_, enthalpy), multiple)| {
+            (multiple / &scale).to_f64() * enthalpy
+        })
+        .sum())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::model::ReactionBuilder;
+    use crate::thermo::enthalpy_of_reaction;
+    #[cfg(feature = "balance")]
+    use crate::{
+        model::BalancedReaction, parse::parse_equation, solve::balance,
+        thermo::hess_law,
+    };
+
+    #[cfg(feature = "balance")]
+    fn reaction(equation: &str) -> BalancedReaction {
+        let (rg, pd) = parse_equation(equation).unwrap();
+        balance(rg, pd).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn carbon_monoxide_formation() {
+        let steps = vec![
+            (reaction("C(s) + O2(g) = CO2(g)"), -393.5),
+            (reaction("CO(g) + O2(g) = CO2(g)"), -566.0),
+        ];
+        // C + O2 -> CO2 minus half of 2 CO + O2 -> 2 CO2
+        let target = reaction("C(s) + O2(g) = CO(g)");
+        assert_eq!(target.to_string(), "2 C(s) + O2(g) -> 2 CO(g)");
+        let enthalpy = hess_law(&steps, &target).unwrap();
+        assert!((enthalpy - -221.0).abs() < 1e-9, "{}", enthalpy);
+    }
+
+    #[test]
+    #[cfg(feature = "balance")]
+    fn not_a_combination() {
+        let steps = vec![(reaction("C(s) + O2(g) = CO2(g)"), -393.5)];
+        let target = reaction("H2(g) + O2(g) = H2O(l)");
+        assert!(hess_law(&steps, &target).is_err());
+    }
+
+    #[test]
+    fn methane_combustion() {
+        let reaction = ReactionBuilder::new()
+            .add_reagent("CH4(g)", 1)
+            .add_reagent("O2(g)", 2)
+            .add_product("CO2(g)", 1)
+            .add_product("H2O(l)", 2)
+            .build()
+            .unwrap();
+        let mut formation: HashMap<String, f64> = [
+            ("CH4(g)", -74.8),
+            ("O2(g)", 0.0),
+            ("CO2(g)", -393.5),
+            ("H2O(l)", -285.8),
+        ]
+        .iter()
+        .map(|(f, h)| (f.to_string(), *h))
+        .collect();
+        let enthalpy = enthalpy_of_reaction(&reaction, &formation).unwrap();
+        assert!((enthalpy - -890.3).abs() < 1e-9, "{}", enthalpy);
+        formation.remove("O2(g)");
+        let err = enthalpy_of_reaction(&reaction, &formation).unwrap_err();
+        assert!(err.to_string().contains("O2(g)"), "{}", err);
+    }
+}
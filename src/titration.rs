@@ -0,0 +1,90 @@
+use crate::error::StoichError;
+use crate::solution::dilution;
+
+fn check_factor(n: u32) -> Result<(), StoichError> {
+    match n > 0 {
+        true => Ok(()),
+        false => Err(StoichError::InvalidInput(
+            "Equivalence factor must be at least 1".to_string(),
+        )),
+    }
+}
+
+// Normality, in equivalents per liter, of a solution whose formula units
+// each give `n` H+ or OH-.
+fn normality(molarity: f64, n: u32) -> Result<f64, StoichError> {
+    if molarity.is_nan() || molarity <= 0.0 {
+        return Err(StoichError::InvalidInput(format!(
+            "Molarity must be positive, got {}",
+            molarity
+        )));
+    }
+    check_factor(n)?;
+    Ok(molarity * n as f64)
+}
+
+/// Volume of titrant needed to reach the equivalence point, in the units of
+/// `analyte_volume`. `analyte_n` and `titrant_n` count the H+ or OH- each
+/// formula unit gives, e.g. 2 for H2SO4.
+pub fn equivalence_volume(
+    analyte_molarity: f64,
+    analyte_volume: f64,
+    titrant_molarity: f64,
+    analyte_n: u32,
+    titrant_n: u32,
+) -> Result<f64, StoichError> {
+    dilution(
+        Some(normality(analyte_molarity, analyte_n)?),
+        Some(analyte_volume),
+        Some(normality(titrant_molarity, titrant_n)?),
+        None,
+    )
+}
+
+/// Molarity of the analyte when `titrant_volume` of titrant reached the
+/// equivalence point, with volumes in the same units.
+pub fn analyte_molarity(
+    titrant_molarity: f64,
+    titrant_volume: f64,
+    analyte_volume: f64,
+    analyte_n: u32,
+    titrant_n: u32,
+) -> Result<f64, StoichError> {
+    check_factor(analyte_n)?;
+    let analyte_normality = dilution(
+        None,
+        Some(analyte_volume),
+        Some(normality(titrant_molarity, titrant_n)?),
+        Some(titrant_volume),
+    )?;
+    Ok(analyte_normality / analyte_n as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::titration::{analyte_molarity, equivalence_volume};
+
+    #[test]
+    fn hydrochloric_acid_with_sodium_hydroxide() {
+        let v = equivalence_volume(0.1, 25.0, 0.1, 1, 1).unwrap();
+        assert!((v - 25.0).abs() < 1e-9, "{}", v);
+        let m = analyte_molarity(0.1, 20.0, 25.0, 1, 1).unwrap();
+        assert!((m - 0.08).abs() < 1e-9, "{}", m);
+    }
+
+    #[test]
+    fn polyprotic_acid() {
+        // H2SO4 + 2 NaOH, and H3PO4 against Ba(OH)2.
+        let v = equivalence_volume(0.1, 25.0, 0.1, 2, 1).unwrap();
+        assert!((v - 50.0).abs() < 1e-9, "{}", v);
+        let m = analyte_molarity(0.1, 30.0, 20.0, 3, 2).unwrap();
+        assert!((m - 0.1).abs() < 1e-9, "{}", m);
+    }
+
+    #[test]
+    fn invalid_titration() {
+        assert!(equivalence_volume(0.1, 25.0, 0.0, 1, 1).is_err());
+        assert!(equivalence_volume(0.1, 25.0, 0.1, 0, 1).is_err());
+        assert!(analyte_molarity(0.1, 20.0, 0.0, 1, 1).is_err());
+    }
+}